@@ -0,0 +1,220 @@
+use crate::*;
+
+/// Styling that can be applied to a run of text
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextAttribute {
+    Color(Color),
+    /// Fill behind the text. Direct2D can't draw this, so it is ignored on Windows
+    Background(Color),
+    Weight(FontWeight),
+    Style(FontStyle),
+    Underline(bool),
+    Size(f32),
+    /// The name of the font family to use, looked up like the name given to `new_font`
+    Family(String)
+}
+
+impl TextAttribute {
+    /// Returns true if `other` sets the same property, possibly to a different value
+    pub fn same_kind(&self, other: &TextAttribute) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// An attribute applied to the bytes of text in `range`
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextRun {
+    pub range: Range<usize>,
+    pub attribute: TextAttribute
+}
+
+/// Text with runs of styling, that can be turned into a TextLayout with
+/// `RenderContextExt::new_attributed_text_layout`. Ranges are in bytes, like indices into a `str`,
+/// and must lie on character boundaries. Runs of the same kind of attribute never overlap, so
+/// setting an attribute replaces whatever was there before
+#[derive(Clone,Debug,Default,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedAttributedString"))]
+pub struct AttributedString {
+    text: String,
+    runs: Vec<TextRun>
+}
+
+impl AttributedString {
+    /// Create an attributed string with no styling
+    pub fn new(text: &str) -> AttributedString {
+        AttributedString { text: text.to_string(), runs: Vec::new() }
+    }
+
+    /// Create an attributed string with `runs` applied in order, or an error if one isn't a range
+    /// of `text`
+    pub fn with_runs(text: &str, runs: &[TextRun]) -> Result<AttributedString, Box<dyn Error>> {
+        let mut s = AttributedString::new(text);
+        for r in runs {
+            if r.range.start > r.range.end || r.range.end > text.len() || !text.is_char_boundary(r.range.start)
+                || !text.is_char_boundary(r.range.end) {
+                return Err(format!("run {:?} isn't a range of the text", r.range).into());
+            }
+            s.set_attribute(r.range.clone(), r.attribute.clone());
+        }
+        Ok(s)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The runs of styling, ordered by where they start
+    pub fn runs(&self) -> &[TextRun] {
+        &self.runs
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Set `attribute` over `range`, and return the string
+    pub fn with(mut self, range: Range<usize>, attribute: TextAttribute) -> AttributedString {
+        self.set_attribute(range, attribute);
+        self
+    }
+
+    /// Set `attribute` over `range`, replacing any attribute of the same kind there
+    pub fn set_attribute(&mut self, range: Range<usize>, attribute: TextAttribute) {
+        self.check_range(&range);
+        self.cut(&range, |a| a.same_kind(&attribute));
+        if range.is_empty() {
+            return;
+        }
+        // join up with runs of the same value on either side
+        let mut range = range;
+        if let Some(i) = self.runs.iter().position(|r| r.range.end == range.start && r.attribute == attribute) {
+            range.start = self.runs.remove(i).range.start;
+        }
+        if let Some(i) = self.runs.iter().position(|r| r.range.start == range.end && r.attribute == attribute) {
+            range.end = self.runs.remove(i).range.end;
+        }
+        self.insert_run(TextRun { range, attribute });
+    }
+
+    /// Remove every attribute from `range`
+    pub fn clear_attributes(&mut self, range: Range<usize>) {
+        self.check_range(&range);
+        self.cut(&range, |_| true);
+    }
+
+    /// The attributes that apply to the character starting at byte `index`
+    pub fn attributes_at(&self, index: usize) -> Vec<&TextAttribute> {
+        self.runs.iter().filter(|r| r.range.contains(&index)).map(|r| &r.attribute).collect()
+    }
+
+    /// Append `text` with `attributes` applied to all of it
+    pub fn push_str(&mut self, text: &str, attributes: &[TextAttribute]) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        for a in attributes {
+            self.set_attribute(start..self.text.len(), a.clone());
+        }
+    }
+
+    /// Insert `text` at byte `index`. Runs that continue past or end at `index` are extended over
+    /// it, so typing at the end of a bold word keeps it bold
+    pub fn insert(&mut self, index: usize, text: &str) {
+        self.text.insert_str(index, text);
+        for r in self.runs.iter_mut() {
+            if r.range.start >= index {
+                r.range.start += text.len();
+            }
+            if r.range.end >= index {
+                r.range.end += text.len();
+            }
+        }
+    }
+
+    /// Remove the text in `range`, shrinking the runs that cover it
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.check_range(&range);
+        self.text.replace_range(range.clone(), "");
+        let map = |i: usize| if i <= range.start { i } else if i >= range.end { i - range.len() } else { range.start };
+        for r in self.runs.iter_mut() {
+            r.range = map(r.range.start)..map(r.range.end);
+        }
+        self.runs.retain(|r| !r.range.is_empty());
+        // runs on either side of the removed text can now touch
+        let runs = std::mem::take(&mut self.runs);
+        for r in runs {
+            self.set_attribute(r.range, r.attribute);
+        }
+    }
+
+    /// Replace the text in `range` with `text`, which takes the styling of the start of `range`
+    pub fn replace_range(&mut self, range: Range<usize>, text: &str) {
+        let start = range.start;
+        self.remove(range);
+        self.insert(start, text);
+    }
+
+    fn check_range(&self, range: &Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.text.len(), "range {:?} is out of bounds", range);
+        assert!(self.text.is_char_boundary(range.start) && self.text.is_char_boundary(range.end),
+                "range {:?} isn't on character boundaries", range);
+    }
+
+    // remove `range` from the runs with attributes matching `pred`, splitting those that cover it
+    fn cut<F: Fn(&TextAttribute) -> bool>(&mut self, range: &Range<usize>, pred: F) {
+        let mut split = Vec::new();
+        for r in self.runs.iter_mut() {
+            if !pred(&r.attribute) || r.range.end <= range.start || r.range.start >= range.end {
+                continue;
+            }
+            if r.range.end > range.end {
+                split.push(TextRun { range: range.end..r.range.end, attribute: r.attribute.clone() });
+            }
+            r.range.end = r.range.end.min(range.start);
+        }
+        self.runs.retain(|r| !r.range.is_empty());
+        for r in split {
+            self.insert_run(r);
+        }
+    }
+
+    fn insert_run(&mut self, run: TextRun) {
+        let i = self.runs.iter().rposition(|r| r.range.start <= run.range.start).map_or(0, |i| i + 1);
+        self.runs.insert(i, run);
+    }
+}
+
+impl From<&str> for AttributedString {
+    fn from(text: &str) -> AttributedString {
+        AttributedString::new(text)
+    }
+}
+
+impl From<String> for AttributedString {
+    fn from(text: String) -> AttributedString {
+        AttributedString { text, runs: Vec::new() }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UncheckedAttributedString {
+    text: String,
+    #[serde(default)]
+    runs: Vec<TextRun>
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<UncheckedAttributedString> for AttributedString {
+    type Error = String;
+
+    fn try_from(s: UncheckedAttributedString) -> Result<AttributedString, String> {
+        AttributedString::with_runs(&s.text, &s.runs).map_err(|e| e.to_string())
+    }
+}
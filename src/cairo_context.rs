@@ -1,15 +1,34 @@
 use crate::*;
 
 use std::error::Error;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_void;
-use std::mem::transmute;
+use std::os::raw::c_char;
+use std::os::unix::ffi::OsStrExt;
+use std::mem::{transmute, MaybeUninit};
 use std::rc::Rc;
+use std::cell::Cell;
 
 use cairo_sys::*;
 use pango_sys::*;
 use pango_cairo_sys::*;
 use gobject_sys::{g_object_unref, g_object_ref};
 
+extern "C" {
+    fn cairo_surface_write_to_png(surface: *mut cairo_surface_t, filename: *const c_char) -> cairo_status_t;
+    fn cairo_image_surface_create_from_png(filename: *const c_char) -> *mut cairo_surface_t;
+}
+
+// only exposed by cairo-sys with cairo 1.14 features enabled
+extern "C" {
+    pub(crate) fn cairo_surface_set_device_scale(surface: *mut cairo_surface_t, x_scale: f64, y_scale: f64);
+    pub(crate) fn cairo_surface_get_device_scale(surface: *mut cairo_surface_t, x_scale: *mut f64, y_scale: *mut f64);
+}
+
+fn path_to_cstring(path: &std::path::Path) -> Result<CString, Box<dyn Error>> {
+    CString::new(path.as_os_str().as_bytes()).map_err(Into::into)
+}
+
 struct PangoFontDesc(*mut PangoFontDescription);
 
 impl Drop for PangoFontDesc {
@@ -55,37 +74,237 @@ fn convert_style(style: FontStyle) -> pango_sys::PangoStyle {
     }
 }
 
-pub struct TextLayout(Rc<GObject<PangoLayout>>);
+impl FontExt for Font {
+    fn description(&self) -> FontDescription {
+        unsafe {
+            let fd = (self.0).0;
+            let family = pango_font_description_get_family(fd);
+            let name = if family.is_null() { String::new() } else { CStr::from_ptr(family).to_string_lossy().into_owned() };
+            let weight = match pango_font_description_get_weight(fd) {
+                w if w <= PANGO_WEIGHT_LIGHT => FontWeight::Light,
+                w if w >= PANGO_WEIGHT_SEMIBOLD => FontWeight::Bold,
+                _ => FontWeight::Regular
+            };
+            let style = match pango_font_description_get_style(fd) {
+                PANGO_STYLE_NORMAL => FontStyle::Normal,
+                _ => FontStyle::Italic
+            };
+            FontDescription {
+                name, weight, style,
+                size: pango_font_description_get_size(fd) as f32 / PANGO_SCALE as f32
+            }
+        }
+    }
+
+    fn metrics(&self) -> FontMetrics {
+        let ps = 1.0 / PANGO_SCALE as f32;
+        unsafe {
+            let pg = pango_font_map_create_context(pango_cairo_font_map_get_default());
+            let m = pango_context_get_metrics(pg, (self.0).0, std::ptr::null_mut());
+            // Pango doesn't measure the x-height, so it is taken from the ink of an x
+            let ly = pango_layout_new(pg);
+            pango_layout_set_font_description(ly, (self.0).0);
+            pango_layout_set_text(ly, b"x".as_ptr() as *const c_char, 1);
+            let mut ink = PangoRectangle { x: 0, y: 0, width: 0, height: 0 };
+            pango_layout_get_extents(ly, &mut ink, std::ptr::null_mut());
+            let metrics = FontMetrics {
+                ascent: pango_font_metrics_get_ascent(m) as f32 * ps,
+                descent: pango_font_metrics_get_descent(m) as f32 * ps,
+                x_height: (pango_layout_get_baseline(ly) - ink.y) as f32 * ps
+            };
+            pango_font_metrics_unref(m);
+            g_object_unref(ly as *mut gobject_sys::GObject);
+            g_object_unref(pg as *mut gobject_sys::GObject);
+            metrics
+        }
+    }
+}
+
+pub struct TextLayout(Rc<GObject<PangoLayout>>, Rc<Cell<LayoutFit>>);
 
 impl Clone for TextLayout {
     fn clone(&self) -> Self {
-        TextLayout(self.0.clone())
+        TextLayout(self.0.clone(), self.1.clone())
     }
 }
+
+/// How a layout fits its text into the size it was created with. Pango only has one width and
+/// height, which are changed depending on the rest, so they are kept here
+#[derive(Copy,Clone)]
+struct LayoutFit {
+    width: f32, height: f32,
+    wrap: WrapMode,
+    ellipsize: EllipsizeMode,
+    max_lines: Option<u32>,
+    /// Pango can't space out paragraphs, so each line is moved down by this much for every
+    /// paragraph that starts above it
+    paragraph_spacing: f32
+}
+
+/// A line of a pango layout, with its extents in Pango units
+struct LineExtents {
+    line: *mut PangoLayoutLine,
+    logical: PangoRectangle,
+    baseline: i32,
+    /// How far the line is moved down by the paragraph spacing
+    offset: i32
+}
     
+impl TextLayout {
+    /// Apply `attr` to the bytes in `range`, replacing any attribute of the same kind there
+    unsafe fn change_attribute(&self, range: Range<u32>, attr: *mut PangoAttribute) {
+        let mut attrs = pango_layout_get_attributes((self.0).0);
+        if attrs.is_null() {
+            attrs = pango_attr_list_new();
+            pango_layout_set_attributes((self.0).0, attrs);
+        }
+        (*attr).start_index = range.start;
+        (*attr).end_index = range.end;
+        pango_attr_list_change(attrs, attr);
+        // the height of the lines might have changed
+        if self.1.get().max_lines.is_some() {
+            self.set_fit(self.1.get());
+        }
+    }
+
+    fn set_fit(&self, fit: LayoutFit) {
+        self.1.set(fit);
+        let ly = (self.0).0;
+        unsafe {
+            pango_layout_set_ellipsize(ly, PANGO_ELLIPSIZE_NONE);
+            pango_layout_set_height(ly, -1);
+            if fit.wrap == WrapMode::None && fit.ellipsize == EllipsizeMode::None {
+                pango_layout_set_width(ly, -1);
+                return;
+            }
+            pango_layout_set_width(ly, (fit.width * PANGO_SCALE as f32) as i32);
+            pango_layout_set_wrap(ly, match fit.wrap {
+                WrapMode::Word | WrapMode::None => PANGO_WRAP_WORD,
+                WrapMode::Char => PANGO_WRAP_CHAR,
+                WrapMode::WordChar => PANGO_WRAP_WORD_CHAR
+            });
+            let ellipsize = match fit.ellipsize {
+                EllipsizeMode::None => return,
+                EllipsizeMode::Start => PANGO_ELLIPSIZE_START,
+                EllipsizeMode::Middle => PANGO_ELLIPSIZE_MIDDLE,
+                EllipsizeMode::End => PANGO_ELLIPSIZE_END
+            };
+            // a negative height limits each paragraph to that many lines, so unwrapped text is
+            // ellipsized to one line per paragraph
+            let height = if fit.wrap == WrapMode::None {
+                -1
+            } else {
+                let height = self.spaced_height((fit.height * PANGO_SCALE as f32) as i32);
+                fit.max_lines.map_or(height, |n| height.min(self.lines_bottom(n)))
+            };
+            pango_layout_set_height(ly, height);
+            pango_layout_set_ellipsize(ly, ellipsize);
+        }
+    }
+
+    // the bottom of the first `n` lines, in Pango units
+    unsafe fn lines_bottom(&self, n: u32) -> i32 {
+        let lines = self.line_extents();
+        match n as usize {
+            0 => 0,
+            n if n < lines.len() => lines[n - 1].logical.y + lines[n - 1].logical.height,
+            _ => i32::MAX
+        }
+    }
+
+    // the height Pango should fit the lines into, so they still fit in `height` once they are
+    // spaced out. The first line is always kept, like Pango does
+    unsafe fn spaced_height(&self, height: i32) -> i32 {
+        if self.1.get().paragraph_spacing == 0.0 {
+            return height;
+        }
+        let lines = self.line_extents();
+        let fits = lines.iter().skip(1).take_while(|l| l.logical.y + l.logical.height + l.offset <= height).count();
+        let bottom = lines[fits].logical.y + lines[fits].logical.height;
+        // when every line fits, none of them can be cut off
+        if fits + 1 == lines.len() { height.max(bottom) } else { bottom }
+    }
+
+    unsafe fn line_extents(&self) -> Vec<LineExtents> {
+        let spacing = (self.1.get().paragraph_spacing * PANGO_SCALE as f32) as i32;
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        let iter = pango_layout_get_iter((self.0).0);
+        loop {
+            let line = pango_layout_iter_get_line_readonly(iter);
+            // the flag shares its word with the line's direction
+            if !lines.is_empty() && (*line).is_paragraph_start & 1 != 0 {
+                offset += spacing;
+            }
+            let mut logical = PangoRectangle { x: 0, y: 0, width: 0, height: 0 };
+            pango_layout_iter_get_line_extents(iter, std::ptr::null_mut(), &mut logical);
+            lines.push(LineExtents { line, logical, baseline: pango_layout_iter_get_baseline(iter), offset });
+            if pango_layout_iter_next_line(iter) == 0 {
+                break;
+            }
+        }
+        pango_layout_iter_free(iter);
+        lines
+    }
+
+    // color_range without the RenderContext, which Pango doesn't need
+    fn foreground_range(&self, range: Range<u32>, col: Color) {
+        unsafe { self.change_attribute(range, pango_attr_foreground_new((col.r*65535.0) as u16, (col.g*65535.0) as u16, (col.b*65535.0) as u16)); }
+    }
+}
+
 impl TextLayoutExt for TextLayout {
     fn bounds(&self) -> Rect {
         let mut w = 0i32;
         let mut h = 0i32;
-        unsafe {
+        let spacing = unsafe {
             pango_layout_get_pixel_size((self.0).0, &mut w, &mut h);
-        }
-        Rect::xywh(0.0, 0.0, w as f32, h as f32)
+            self.line_extents().last().map_or(0, |l| l.offset)
+        };
+        Rect::xywh(0.0, 0.0, w as f32, h as f32 + spacing as f32 / PANGO_SCALE as f32)
     }
     fn char_bounds(&self, index: usize) -> Rect {
             let mut rect: PangoRectangle = PangoRectangle{x:0,y:0,width:0,height:0};
         unsafe {
             pango_layout_index_to_pos((self.0).0, index as i32, &mut rect);
+            let mut line = 0;
+            pango_layout_index_to_line_x((self.0).0, index as i32, 0, &mut line, std::ptr::null_mut());
+            rect.y += self.line_extents().get(line as usize).map_or(0, |l| l.offset);
         }
         let ps = 1.0 / PANGO_SCALE as f32;
         Rect::xywh(rect.x as f32 * ps, rect.y as f32 * ps, rect.width as f32 * ps, rect.height as f32 * ps)
     }
     
+    fn lines(&self) -> Vec<LineMetrics> {
+        let ps = 1.0 / PANGO_SCALE as f32;
+        unsafe {
+            self.line_extents().iter().map(|l| {
+                let start = (*l.line).start_index as usize;
+                let rect = &l.logical;
+                LineMetrics {
+                    range: start..start + (*l.line).length as usize,
+                    bounds: Rect::xywh(rect.x as f32 * ps, (rect.y + l.offset) as f32 * ps, rect.width as f32 * ps, rect.height as f32 * ps),
+                    baseline: (l.baseline + l.offset) as f32 * ps
+                }
+            }).collect()
+        }
+    }
+
     fn hit_test(&self, p: Point) -> Option<(usize, Rect)> {
         let mut index: i32 = 0;
         let mut trailing : i32 = 0;
         unsafe {
-            if pango_layout_xy_to_index((self.0).0, (p.x * PANGO_SCALE as f32) as i32, (p.y * PANGO_SCALE as f32) as i32, &mut index, &mut trailing) > 0 {
+            // undo the paragraph spacing of the line the point is on, counting the space above a
+            // paragraph as part of the line before it
+            let mut y = (p.y * PANGO_SCALE as f32) as i32;
+            let lines = self.line_extents();
+            if let Some(i) = lines.iter().rposition(|l| l.logical.y + l.offset <= y) {
+                y -= lines[i].offset;
+                if i + 1 < lines.len() {
+                    y = y.min(lines[i].logical.y + lines[i].logical.height - 1);
+                }
+            }
+            if pango_layout_xy_to_index((self.0).0, (p.x * PANGO_SCALE as f32) as i32, y, &mut index, &mut trailing) > 0 {
                 Some((index as usize, self.char_bounds(index as usize)))
             } else {
                 None
@@ -94,91 +313,425 @@ impl TextLayoutExt for TextLayout {
     }
 
     fn color_range(&self, _: &RenderContext, range: Range<u32>, col: Color) {
-        unsafe {
-            let mut attrs = pango_layout_get_attributes((self.0).0);
-            if attrs == std::ptr::null_mut() {
-                attrs = pango_attr_list_new();
-                pango_layout_set_attributes((self.0).0, attrs);
-            }
-            let mut attr = pango_attr_foreground_new((col.r*65535.0) as u16, (col.g*65535.0) as u16, (col.b*65535.0) as u16);
-            (*attr).start_index = range.start;
-            (*attr).end_index = range.end;
-            pango_attr_list_change(attrs, attr);
-        }
+        self.foreground_range(range, col);
     }
     fn style_range(&self, range: Range<u32>, style: FontStyle) {
-        unsafe {
-            let mut attrs = pango_layout_get_attributes((self.0).0);
-            if attrs == std::ptr::null_mut() {
-                attrs = pango_attr_list_new();
-                pango_layout_set_attributes((self.0).0, attrs);
-            }
-            let mut attr = pango_attr_style_new(convert_style(style));
-            (*attr).start_index = range.start;
-            (*attr).end_index = range.end;
-            pango_attr_list_change(attrs, attr);
-        }
+        unsafe { self.change_attribute(range, pango_attr_style_new(convert_style(style))); }
     }
     fn weight_range(&self, range: Range<u32>, weight: FontWeight) {
+        unsafe { self.change_attribute(range, pango_attr_weight_new(convert_weight(weight))); }
+    }
+    fn underline_range(&self, range: Range<u32>, ul: bool) {
+        unsafe { self.change_attribute(range, pango_attr_underline_new(if ul { PANGO_UNDERLINE_SINGLE } else { PANGO_UNDERLINE_NONE })); }
+    }
+    fn size_range(&self, range: Range<u32>, size: f32) {
+        unsafe { self.change_attribute(range, pango_attr_size_new((size * PANGO_SCALE as f32) as i32)); }
+    }
+    fn background_range(&self, range: Range<u32>, col: Color) {
+        unsafe { self.change_attribute(range, pango_attr_background_new((col.r*65535.0) as u16, (col.g*65535.0) as u16, (col.b*65535.0) as u16)); }
+    }
+    fn family_range(&self, range: Range<u32>, family: &str) {
+        let family = match CString::new(family) { Ok(f) => f, Err(_) => return };
+        // Pango copies the family name
+        unsafe { self.change_attribute(range, pango_attr_family_new(family.as_ptr())); }
+    }
+
+    fn set_wrap(&self, wrap: WrapMode) {
+        self.set_fit(LayoutFit { wrap, ..self.1.get() });
+    }
+    fn set_ellipsize(&self, ellipsize: EllipsizeMode) {
+        self.set_fit(LayoutFit { ellipsize, ..self.1.get() });
+    }
+    fn set_max_lines(&self, max_lines: Option<u32>) {
+        self.set_fit(LayoutFit { max_lines, ..self.1.get() });
+    }
+    fn set_paragraph_style(&self, style: &ParagraphStyle) {
+        let ly = (self.0).0;
         unsafe {
-            let mut attrs = pango_layout_get_attributes((self.0).0);
-            if attrs == std::ptr::null_mut() {
-                attrs = pango_attr_list_new();
-                pango_layout_set_attributes((self.0).0, attrs);
+            pango_layout_set_alignment(ly, match style.alignment {
+                TextAlignment::Left | TextAlignment::Justify => PANGO_ALIGN_LEFT,
+                TextAlignment::Center => PANGO_ALIGN_CENTER,
+                TextAlignment::Right => PANGO_ALIGN_RIGHT
+            });
+            pango_layout_set_justify(ly, (style.alignment == TextAlignment::Justify) as i32);
+            set_line_spacing(ly, style.line_spacing);
+            pango_layout_set_indent(ly, (style.first_line_indent * PANGO_SCALE as f32) as i32);
+            if style.tab_stops.is_empty() {
+                pango_layout_set_tabs(ly, std::ptr::null_mut());
+            } else {
+                let tabs = pango_tab_array_new(style.tab_stops.len() as i32, 0);
+                for (i, stop) in style.tab_stops.iter().enumerate() {
+                    pango_tab_array_set_tab(tabs, i as i32, PANGO_TAB_LEFT, (stop * PANGO_SCALE as f32) as i32);
+                }
+                pango_layout_set_tabs(ly, tabs);
+                pango_tab_array_free(tabs);
             }
-            let mut attr = pango_attr_weight_new(convert_weight(weight));
-            (*attr).start_index = range.start;
-            (*attr).end_index = range.end;
-            pango_attr_list_change(attrs, attr);
+            // the fit depends on the height of the lines
+            self.set_fit(LayoutFit { paragraph_spacing: style.paragraph_spacing, ..self.1.get() });
         }
     }
-    fn underline_range(&self, range: Range<u32>, ul: bool) {
+}
+
+// a factor of 0 lays lines out at their natural height without any rounding
+#[cfg(feature = "pango_1_44")]
+unsafe fn set_line_spacing(ly: *mut PangoLayout, factor: f32) {
+    pango_layout_set_line_spacing(ly, if factor == 1.0 { 0.0 } else { factor });
+}
+
+// older Pango can only add space between lines, so the factor is turned into the extra height of
+// a line at its natural spacing
+#[cfg(not(feature = "pango_1_44"))]
+unsafe fn set_line_spacing(ly: *mut PangoLayout, factor: f32) {
+    pango_layout_set_spacing(ly, 0);
+    if factor == 1.0 {
+        return;
+    }
+    let mut logical = MaybeUninit::uninit();
+    pango_layout_line_get_extents(pango_layout_get_line_readonly(ly, 0), std::ptr::null_mut(), logical.as_mut_ptr());
+    let height = logical.assume_init().height as f32;
+    pango_layout_set_spacing(ly, (height * (factor - 1.0)) as i32);
+}
+
+/// Copy the contents of a Cairo image surface into a PixelBuffer, converting from premultiplied
+/// native-endian ARGB to straight RGBA
+unsafe fn image_surface_pixels(img: *mut cairo_surface_t) -> Result<PixelBuffer, Box<dyn Error>> {
+    if cairo_surface_status(img) != STATUS_SUCCESS {
+        return Err("failed to map surface to an image".into());
+    }
+    let format = cairo_image_surface_get_format(img);
+    if format != FORMAT_A_RGB32 && format != FORMAT_RGB24 {
+        return Err("unsupported image surface format".into());
+    }
+    let width = cairo_image_surface_get_width(img) as u32;
+    let height = cairo_image_surface_get_height(img) as u32;
+    let src_stride = cairo_image_surface_get_stride(img) as usize;
+    let src = std::slice::from_raw_parts(cairo_image_surface_get_data(img), src_stride * height as usize);
+    let mut buf = PixelBuffer::new(width, height);
+    for y in 0..height as usize {
+        let row = &src[y*src_stride .. y*src_stride + width as usize*4];
+        let out = &mut buf.data[y*buf.stride .. (y+1)*buf.stride];
+        for (px, o) in row.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+            let argb = u32::from_ne_bytes([px[0], px[1], px[2], px[3]]);
+            let a = if format == FORMAT_RGB24 { 255 } else { argb >> 24 };
+            let unpremultiply = |c: u32| (c * 255 + a / 2).checked_div(a).map_or(0, |v| v.min(255) as u8);
+            o[0] = unpremultiply((argb >> 16) & 0xff);
+            o[1] = unpremultiply((argb >> 8) & 0xff);
+            o[2] = unpremultiply(argb & 0xff);
+            o[3] = a as u8;
+        }
+    }
+    Ok(buf)
+}
+
+/// Create a Cairo image surface containing the pixels in `buf`, converting from straight RGBA to
+/// premultiplied native-endian ARGB
+unsafe fn pixels_image_surface(buf: &PixelBuffer) -> Result<*mut cairo_surface_t, Box<dyn Error>> {
+    let img = cairo_image_surface_create(FORMAT_A_RGB32, buf.width as i32, buf.height as i32);
+    if cairo_surface_status(img) != STATUS_SUCCESS {
+        cairo_surface_destroy(img);
+        return Err("failed to create cairo image surface".into());
+    }
+    cairo_surface_flush(img);
+    let dst_stride = cairo_image_surface_get_stride(img) as usize;
+    let dst = std::slice::from_raw_parts_mut(cairo_image_surface_get_data(img), dst_stride * buf.height as usize);
+    for y in 0..buf.height as usize {
+        let row = &buf.data[y*buf.stride .. y*buf.stride + buf.width as usize*4];
+        let out = &mut dst[y*dst_stride .. y*dst_stride + buf.width as usize*4];
+        for (px, o) in row.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+            let a = px[3] as u32;
+            let premultiply = |c: u8| (c as u32 * a + 127) / 255;
+            let argb = (a << 24) | (premultiply(px[0]) << 16) | (premultiply(px[1]) << 8) | premultiply(px[2]);
+            o.copy_from_slice(&argb.to_ne_bytes());
+        }
+    }
+    cairo_surface_mark_dirty(img);
+    Ok(img)
+}
+
+/// Identifies the pango layout behind a handle, which all of its clones share
+pub(crate) fn layout_id(txl: &TextLayout) -> usize {
+    Rc::as_ptr(&txl.0) as usize
+}
+
+/// Identifies the image surface behind a handle, which all of its clones share
+pub(crate) fn image_id(img: &Image) -> usize {
+    Rc::as_ptr(&img.0) as usize
+}
+
+struct CairoImage(*mut cairo_surface_t);
+
+impl Drop for CairoImage {
+    fn drop(&mut self) {
         unsafe {
-            let mut attrs = pango_layout_get_attributes((self.0).0);
-            if attrs == std::ptr::null_mut() {
-                attrs = pango_attr_list_new();
-                pango_layout_set_attributes((self.0).0, attrs);
-            }
-            let mut attr = pango_attr_underline_new(if ul {
-                PANGO_UNDERLINE_SINGLE
-            } else {
-                PANGO_UNDERLINE_NONE
-            });
-            (*attr).start_index = range.start;
-            (*attr).end_index = range.end;
-            pango_attr_list_change(attrs, attr);
+            cairo_surface_destroy(self.0);
         }
     }
-    fn size_range(&self, range: Range<u32>, size: f32) {
+}
+
+#[derive(Clone)]
+pub struct Image(Rc<CairoImage>);
+
+impl ImageExt for Image {
+    fn bounds(&self) -> Rect {
         unsafe {
-            let mut attrs = pango_layout_get_attributes((self.0).0);
-            if attrs == std::ptr::null_mut() {
-                attrs = pango_attr_list_new();
-                pango_layout_set_attributes((self.0).0, attrs);
-            }
-            let mut attr = pango_attr_size_new((size * PANGO_SCALE as f32) as i32);
-            (*attr).start_index = range.start;
-            (*attr).end_index = range.end;
-            pango_attr_list_change(attrs, attr);
+            let surf = (self.0).0;
+            Rect::wh(cairo_image_surface_get_width(surf) as f32, cairo_image_surface_get_height(surf) as f32)
+        }
+    }
+}
+
+pub fn load_png(path: &std::path::Path) -> Result<PixelBuffer, Box<dyn Error>> {
+    let filename = path_to_cstring(path)?;
+    unsafe {
+        let img = cairo_image_surface_create_from_png(filename.as_ptr());
+        let pixels = image_surface_pixels(img);
+        cairo_surface_destroy(img);
+        pixels.map_err(|_| format!("failed to load PNG file {}", path.display()).into())
+    }
+}
+
+pub fn write_png(buf: &PixelBuffer, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    let filename = path_to_cstring(path)?;
+    unsafe {
+        let img = pixels_image_surface(buf)?;
+        let status = cairo_surface_write_to_png(img, filename.as_ptr());
+        cairo_surface_destroy(img);
+        if status != STATUS_SUCCESS {
+            return Err(format!("failed to write PNG file {}", path.display()).into());
+        }
+    }
+    Ok(())
+}
+
+/// The cairo matrix with the same effect as `m`
+fn affine_matrix(m: &Affine) -> Matrix {
+    Matrix { xx: m.a as f64, yx: m.b as f64, xy: m.c as f64, yy: m.d as f64, x0: m.e as f64, y0: m.f as f64 }
+}
+
+/// Replace the current path of `cx` with `path`
+unsafe fn set_path(cx: *mut cairo_t, path: &Path) {
+    cairo_new_path(cx);
+    for seg in path.segments() {
+        match *seg {
+            PathSegment::MoveTo(p) => cairo_move_to(cx, p.x as f64, p.y as f64),
+            PathSegment::LineTo(p) => cairo_line_to(cx, p.x as f64, p.y as f64),
+            PathSegment::QuadTo(c, p) => {
+                // cairo only has cubic curves, so elevate the quadratic curve to one
+                let (mut x0, mut y0) = (0.0, 0.0);
+                cairo_get_current_point(cx, &mut x0, &mut y0);
+                let (qx, qy, px, py) = (c.x as f64, c.y as f64, p.x as f64, p.y as f64);
+                cairo_curve_to(cx,
+                    x0 + (qx - x0) * 2.0 / 3.0, y0 + (qy - y0) * 2.0 / 3.0,
+                    px + (qx - px) * 2.0 / 3.0, py + (qy - py) * 2.0 / 3.0,
+                    px, py);
+            },
+            PathSegment::CubicTo(c1, c2, p) => cairo_curve_to(cx,
+                c1.x as f64, c1.y as f64, c2.x as f64, c2.y as f64, p.x as f64, p.y as f64),
+            PathSegment::Arc { center, radius, start_angle, end_angle } => {
+                if end_angle < start_angle {
+                    cairo_arc_negative(cx, center.x as f64, center.y as f64, radius as f64,
+                                       start_angle as f64, end_angle as f64);
+                } else {
+                    cairo_arc(cx, center.x as f64, center.y as f64, radius as f64,
+                              start_angle as f64, end_angle as f64);
+                }
+            },
+            PathSegment::Close => cairo_close_path(cx),
+        }
+    }
+}
+
+/// Blur a buffer of 8-bit alpha values with three box blurs, which together closely approximate
+/// a Gaussian blur with standard deviation `sigma`
+fn blur_alpha(data: &mut [u8], width: usize, height: usize, stride: usize, sigma: f32) {
+    // three boxes of radius r have a combined variance of (r^2 + r)
+    let r = (((4.0 * sigma * sigma + 1.0).sqrt() - 1.0) / 2.0).round() as usize;
+    if r == 0 {
+        return;
+    }
+    let mut line = Vec::new();
+    for _ in 0..3 {
+        for y in 0..height {
+            box_blur_line(data, y * stride, 1, width, r, &mut line);
+        }
+        for x in 0..width {
+            box_blur_line(data, x, stride, height, r, &mut line);
         }
     }
 }
 
+/// Blur `len` values spaced `step` apart starting at `start` with a box of radius `r`, treating
+/// everything outside of them as zero
+fn box_blur_line(data: &mut [u8], start: usize, step: usize, len: usize, r: usize, line: &mut Vec<u32>) {
+    line.clear();
+    line.extend((0..len).map(|i| data[start + i * step] as u32));
+    let size = (2 * r + 1) as u32;
+    let mut sum: u32 = line.iter().take(r).sum();
+    for i in 0..len {
+        if i + r < len {
+            sum += line[i + r];
+        }
+        data[start + i * step] = ((sum + size / 2) / size) as u8;
+        if i >= r {
+            sum -= line[i - r];
+        }
+    }
+}
 
 pub trait CairoSurface {
     fn new(win: &mut Window) -> Result<Self, Box<Error>> where Self: Sized;
+    /// Create a surface that isn't attached to any window, `w` by `h` pixels in size
+    fn new_offscreen(w: u32, h: u32) -> Result<Self, Box<dyn Error>> where Self: Sized;
+    /// Create a surface that renders into a vector document file, `w` by `h` points in size
+    fn new_document(path: &std::path::Path, w: u32, h: u32, format: DocumentFormat) -> Result<Self, Box<dyn Error>> where Self: Sized;
+    /// Create an offscreen surface `w` by `h` pixels in size that can be drawn into this one
+    /// efficiently, with the same scale
+    fn new_similar(&self, w: u32, h: u32) -> Result<Self, Box<dyn Error>> where Self: Sized;
     fn start_paint(&mut self);
     fn end_paint(&mut self);
-    fn resize(&mut self, w: u32, h: u32);
+    /// Resize the surface to `w` by `h` pixels, keeping the old surface if that fails
+    fn resize(&mut self, w: u32, h: u32) -> Result<(), Box<dyn Error>>;
     fn surface(&self) -> *mut cairo_surface_t;
+    /// The size of the surface in points
     fn bounds(&self) -> Rect;
     fn pixels_to_points(&self, p: Point) -> Point;
+    /// The number of pixels per point
+    fn scale(&self) -> f32;
+    fn set_scale(&mut self, scale: f32);
+    /// Returns true if the surface still has the previous frame in it when painting starts, so
+    /// that only part of it needs to be redrawn
+    fn keeps_contents(&self) -> bool { true }
+}
+
+enum Pushed {
+    Clip,
+    Layer(f32, BlendMode)
 }
 
 pub struct CairoRenderContext<S: CairoSurface> {
     surface: S,
     cx: *mut cairo_t,
     pg: *mut PangoContext,
+    stroke_style: StrokeStyle,
+    brush: Brush,
+    // each clip and layer saves the cairo state when it's pushed
+    pushed: Vec<Pushed>,
+    saved_transforms: Vec<Affine>,
+    damaged: Option<Rect>,
+}
+
+impl<S: CairoSurface> CairoRenderContext<S> {
+    fn from_surface(surface: S) -> CairoRenderContext<S> {
+        unsafe {
+            let cx = cairo_create(surface.surface());
+            let pg = pango_cairo_create_context(cx);
+            CairoRenderContext {
+                surface, cx, pg, stroke_style: StrokeStyle::default(), brush: Brush::Solid(Color::black()), pushed: Vec::new(), saved_transforms: Vec::new(), damaged: None
+            }
+        }
+    }
+
+    /// Create a new cairo context after the surface changed, keeping the current brush
+    fn recreate_context(&mut self) {
+        unsafe { 
+            cairo_destroy(self.cx);
+            self.cx = cairo_create(self.surface.surface());
+            g_object_unref(transmute(self.pg));
+            self.pg = pango_cairo_create_context(self.cx);
+            self.apply_brush();
+        }
+        self.pushed.clear();
+        self.saved_transforms.clear();
+    }
+
+    /// Make the current brush the source for drawing operations
+    unsafe fn apply_brush(&self) {
+        let (pattern, stops, extend) = match self.brush {
+            Brush::Solid(col) => {
+                cairo_set_source_rgba(self.cx, col.r as f64, col.g as f64, col.b as f64, col.a as f64);
+                return;
+            },
+            Brush::LinearGradient { start, end, ref stops, extend } =>
+                (cairo_pattern_create_linear(start.x as f64, start.y as f64, end.x as f64, end.y as f64), stops, extend),
+            Brush::RadialGradient { center, radius, ref stops, extend } =>
+                (cairo_pattern_create_radial(center.x as f64, center.y as f64, 0.0,
+                                             center.x as f64, center.y as f64, radius as f64), stops, extend)
+        };
+        for stop in stops {
+            let col = stop.color;
+            cairo_pattern_add_color_stop_rgba(pattern, stop.offset as f64, col.r as f64, col.g as f64, col.b as f64, col.a as f64);
+        }
+        cairo_pattern_set_extend(pattern, match extend {
+            ExtendMode::Pad => EXTEND_PAD,
+            ExtendMode::Repeat => EXTEND_REPEAT,
+            ExtendMode::Reflect => EXTEND_REFLECT
+        });
+        cairo_set_source(self.cx, pattern);
+        cairo_pattern_destroy(pattern);
+    }
+
+    /// Pop the most recently pushed clip or layer, compositing layers, and keep the current
+    /// transform and brush so that only the clip or layer is removed
+    unsafe fn pop_state(&mut self) {
+        let mut m = MaybeUninit::<Matrix>::uninit();
+        cairo_get_matrix(self.cx, m.as_mut_ptr());
+        match self.pushed.pop() {
+            Some(Pushed::Clip) => cairo_restore(self.cx),
+            Some(Pushed::Layer(opacity, blend)) => {
+                let group = cairo_pop_group(self.cx);
+                cairo_save(self.cx);
+                cairo_set_source(self.cx, group);
+                cairo_set_operator(self.cx, match blend {
+                    BlendMode::Normal => OPERATOR_OVER,
+                    BlendMode::Multiply => OPERATOR_MULTIPLY,
+                    BlendMode::Screen => OPERATOR_SCREEN,
+                    BlendMode::Overlay => OPERATOR_OVERLAY,
+                    BlendMode::Darken => OPERATOR_DARKEN,
+                    BlendMode::Lighten => OPERATOR_LIGHTEN,
+                    BlendMode::Difference => OPERATOR_DIFFERENCE
+                });
+                cairo_paint_with_alpha(self.cx, opacity as f64);
+                cairo_restore(self.cx);
+                cairo_pattern_destroy(group);
+            },
+            None => return
+        }
+        cairo_set_matrix(self.cx, m.as_ptr());
+        self.apply_brush();
+    }
+
+    /// Set up the line width and the current stroke style before stroking. The dash pattern is
+    /// scaled by the width, so it has to be applied for every stroke
+    unsafe fn begin_stroke(&self, stroke_width: f32) {
+        let style = &self.stroke_style;
+        cairo_set_line_width(self.cx, stroke_width as f64);
+        cairo_set_line_cap(self.cx, match style.cap {
+            LineCap::Butt => LINE_CAP_BUTT,
+            LineCap::Round => LINE_CAP_ROUND,
+            LineCap::Square => LINE_CAP_SQUARE
+        });
+        cairo_set_line_join(self.cx, match style.join {
+            LineJoin::Miter => LINE_JOIN_MITER,
+            LineJoin::Round => LINE_JOIN_ROUND,
+            LineJoin::Bevel => LINE_JOIN_BEVEL
+        });
+        cairo_set_miter_limit(self.cx, style.miter_limit as f64);
+        if style.is_dashed() {
+            let dashes: Vec<f64> = style.dashes.iter().map(|d| (d * stroke_width) as f64).collect();
+            cairo_set_dash(self.cx, dashes.as_ptr(), dashes.len() as i32, (style.dash_offset * stroke_width) as f64);
+        } else {
+            cairo_set_dash(self.cx, std::ptr::null(), 0, 0.0);
+        }
+    }
+}
+
+impl<S: CairoSurface> Drop for CairoRenderContext<S> {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.pg as *mut _);
+            cairo_destroy(self.cx);
+        }
+    }
 }
 
 impl<S: CairoSurface> RenderContextExt for CairoRenderContext<S> {
@@ -207,25 +760,45 @@ impl<S: CairoSurface> RenderContextExt for CairoRenderContext<S> {
             let ly = pango_layout_new(self.pg);
             pango_layout_set_text(ly, text.as_ptr() as *const i8, text.len() as i32);
             pango_layout_set_font_description(ly, (f.0).0);
-            Ok(TextLayout(Rc::new(GObject(ly))))
+            let txl = TextLayout(Rc::new(GObject(ly)), Rc::new(Cell::new(LayoutFit {
+                width, height, wrap: WrapMode::Word, ellipsize: EllipsizeMode::None, max_lines: None,
+                paragraph_spacing: 0.0
+            })));
+            txl.set_fit(txl.1.get());
+            Ok(txl)
         }
     }
 
+    fn new_attributed_text_layout(&self, text: &AttributedString, f: &Font, width: f32, height: f32) -> Result<TextLayout, Box<dyn Error>> {
+        let txl = self.new_text_layout(text.text(), f, width, height)?;
+        // Pango ranges are in bytes already
+        apply_text_runs(&txl, text, |i| i as u32, |range, col| txl.foreground_range(range, col));
+        Ok(txl)
+    }
+
     fn clear(&mut self, col: Color) {
         unsafe {
             self.set_color(col);
-            //cairo_set_operator(self.cx, enums::Operator::Source);
+            // replace what's there instead of blending with it, so clearing a layer to a
+            // transparent color works
+            cairo_set_operator(self.cx, OPERATOR_SOURCE);
             cairo_paint(self.cx);
+            cairo_set_operator(self.cx, OPERATOR_OVER);
         }
     }
 
     fn set_color(&mut self, col: Color) {
-        unsafe { cairo_set_source_rgba(self.cx, col.r as f64, col.g as f64, col.b as f64, col.a as f64); }
+        self.set_brush(&Brush::Solid(col));
+    }
+
+    fn set_brush(&mut self, brush: &Brush) {
+        self.brush = brush.clone();
+        unsafe { self.apply_brush(); }
     }
 
     fn stroke_rect(&mut self, rect: Rect, stroke_width: f32) {
         unsafe {
-            cairo_set_line_width(self.cx, stroke_width as f64);
+            self.begin_stroke(stroke_width);
             cairo_rectangle(self.cx, rect.x as f64, rect.y as f64, 
                             rect.w as f64, rect.h as f64);
             cairo_stroke(self.cx);
@@ -242,18 +815,120 @@ impl<S: CairoSurface> RenderContextExt for CairoRenderContext<S> {
 
     fn draw_line(&mut self, a: Point, b: Point, stroke_width: f32) {
         unsafe {
-            cairo_set_line_width(self.cx, stroke_width as f64);
+            self.begin_stroke(stroke_width);
             cairo_move_to(self.cx, a.x as f64, a.y as f64);
             cairo_line_to(self.cx, b.x as f64, b.y as f64);
             cairo_stroke(self.cx);
         }
     }
 
+    fn set_stroke_style(&mut self, style: &StrokeStyle) {
+        self.stroke_style = style.clone();
+    }
+
+    fn fill_path(&mut self, path: &Path) {
+        unsafe {
+            set_path(self.cx, path);
+            cairo_fill(self.cx);
+        }
+    }
+
+    fn stroke_path(&mut self, path: &Path, stroke_width: f32) {
+        unsafe {
+            self.begin_stroke(stroke_width);
+            set_path(self.cx, path);
+            cairo_stroke(self.cx);
+        }
+    }
+
+    fn fill_rounded_rect(&mut self, rect: Rect, radii: CornerRadii) {
+        self.fill_path(&Path::rounded_rect(rect, radii));
+    }
+
+    fn stroke_rounded_rect(&mut self, rect: Rect, radii: CornerRadii, stroke_width: f32) {
+        self.stroke_path(&Path::rounded_rect(rect, radii), stroke_width);
+    }
+
+    fn fill_ellipse(&mut self, center: Point, rx: f32, ry: f32) {
+        self.fill_path(&Path::ellipse(center, rx, ry));
+    }
+
+    fn stroke_ellipse(&mut self, center: Point, rx: f32, ry: f32, stroke_width: f32) {
+        self.stroke_path(&Path::ellipse(center, rx, ry), stroke_width);
+    }
+
+    fn draw_shadow(&mut self, shape: &Shape, offset: Point, blur_radius: f32, color: Color) {
+        let path = shape.to_path();
+        // the shadow is drawn into a mask in device pixels, so the blur is measured in them too
+        let ds = self.surface.scale();
+        let m = self.current_transform().multiply(&Affine::scale(ds, ds));
+        let scale = (m.a * m.d - m.b * m.c).abs().sqrt();
+        let sigma = blur_radius.max(0.0) * scale / 2.0;
+        let margin = (sigma * 3.0).ceil() as f64 + 1.0;
+        let (ox, oy) = ((m.a * offset.x + m.c * offset.y) as f64, (m.b * offset.x + m.d * offset.y) as f64);
+        unsafe {
+            set_path(self.cx, &path);
+            let (mut x0, mut y0, mut x1, mut y1) = (0.0, 0.0, 0.0, 0.0);
+            cairo_path_extents(self.cx, &mut x0, &mut y0, &mut x1, &mut y1);
+            cairo_new_path(self.cx);
+            let corners = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)];
+            let (mut left, mut top, mut right, mut bottom) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+            for &(x, y) in corners.iter() {
+                let p = m.transform_point(Point::xy(x as f32, y as f32));
+                left = left.min(p.x as f64 + ox);
+                top = top.min(p.y as f64 + oy);
+                right = right.max(p.x as f64 + ox);
+                bottom = bottom.max(p.y as f64 + oy);
+            }
+            // parts of the shadow outside the surface don't need to be drawn, except where they
+            // blur into it
+            let b = self.surface.bounds();
+            let (b_w, b_h) = (b.w as f64 * ds as f64, b.h as f64 * ds as f64);
+            let left = (left - margin).max(-margin).floor();
+            let top = (top - margin).max(-margin).floor();
+            let right = (right + margin).min(b_w + margin).ceil();
+            let bottom = (bottom + margin).min(b_h + margin).ceil();
+            if right <= left || bottom <= top {
+                return;
+            }
+            let (w, h) = ((right - left) as i32, (bottom - top) as i32);
+
+            let mask = cairo_image_surface_create(FORMAT_A8, w, h);
+            if cairo_surface_status(mask) != STATUS_SUCCESS {
+                cairo_surface_destroy(mask);
+                return;
+            }
+            let mcx = cairo_create(mask);
+            let mut mm = affine_matrix(&m);
+            mm.x0 += ox - left;
+            mm.y0 += oy - top;
+            cairo_set_matrix(mcx, &mm);
+            set_path(mcx, &path);
+            cairo_fill(mcx);
+            cairo_destroy(mcx);
+
+            cairo_surface_flush(mask);
+            let stride = cairo_image_surface_get_stride(mask) as usize;
+            let data = std::slice::from_raw_parts_mut(cairo_image_surface_get_data(mask), stride * h as usize);
+            blur_alpha(data, w as usize, h as usize, stride, sigma);
+            cairo_surface_mark_dirty(mask);
+            cairo_surface_set_device_scale(mask, ds as f64, ds as f64);
+
+            cairo_save(self.cx);
+            cairo_identity_matrix(self.cx);
+            cairo_set_source_rgba(self.cx, color.r as f64, color.g as f64, color.b as f64, color.a as f64);
+            cairo_mask_surface(self.cx, mask, left / ds as f64, top / ds as f64);
+            cairo_restore(self.cx);
+            cairo_surface_destroy(mask);
+        }
+    }
+
     fn draw_text(&mut self, rect: Rect, s: &str, f: &Font) {
         unsafe {
             let ly = pango_layout_new(self.pg);
             pango_layout_set_text(ly, s.as_ptr() as *const i8, s.len() as i32);
             pango_layout_set_font_description(ly, (f.0).0);
+            pango_layout_set_width(ly, (rect.w * PANGO_SCALE as f32) as i32);
             cairo_save(self.cx);
             cairo_move_to(self.cx, rect.x as f64, rect.y as f64);
             pango_cairo_show_layout(self.cx, ly);
@@ -266,8 +941,75 @@ impl<S: CairoSurface> RenderContextExt for CairoRenderContext<S> {
         unsafe {
             pango_cairo_update_layout(self.cx, (txl.0).0);
             cairo_save(self.cx);
-            cairo_move_to(self.cx, p.x as f64, p.y as f64);
-            pango_cairo_show_layout(self.cx, (txl.0).0);
+            if txl.1.get().paragraph_spacing == 0.0 {
+                cairo_move_to(self.cx, p.x as f64, p.y as f64);
+                pango_cairo_show_layout(self.cx, (txl.0).0);
+            } else {
+                // each line is drawn from its baseline, moved down by the paragraph spacing
+                let ps = 1.0 / PANGO_SCALE as f64;
+                for l in txl.line_extents() {
+                    cairo_move_to(self.cx, p.x as f64 + l.logical.x as f64 * ps, p.y as f64 + (l.baseline + l.offset) as f64 * ps);
+                    pango_cairo_show_layout_line(self.cx, l.line);
+                }
+            }
+            cairo_restore(self.cx);
+        }
+    }
+
+    fn new_image(&self, pixels: &PixelBuffer) -> Result<Image, Box<dyn Error>> {
+        unsafe {
+            Ok(Image(Rc::new(CairoImage(pixels_image_surface(pixels)?))))
+        }
+    }
+
+    fn draw_image(&mut self, img: &Image, src: Rect, dst: Rect, opacity: f32, filter: ImageFilter) {
+        if src.w == 0.0 || src.h == 0.0 {
+            return;
+        }
+        unsafe {
+            // saving the state also saves the current brush, which set_source_surface replaces
+            cairo_save(self.cx);
+            cairo_rectangle(self.cx, dst.x as f64, dst.y as f64, dst.w as f64, dst.h as f64);
+            cairo_clip(self.cx);
+            cairo_translate(self.cx, dst.x as f64, dst.y as f64);
+            cairo_scale(self.cx, (dst.w / src.w) as f64, (dst.h / src.h) as f64);
+            // a subsurface keeps pixels outside of src from bleeding into the edges when filtering
+            let sub = cairo_surface_create_for_rectangle((img.0).0, src.x as f64, src.y as f64, src.w as f64, src.h as f64);
+            cairo_set_source_surface(self.cx, sub, 0.0, 0.0);
+            cairo_surface_destroy(sub);
+            let pattern = cairo_get_source(self.cx);
+            cairo_pattern_set_filter(pattern, match filter {
+                ImageFilter::Nearest => FILTER_NEAREST,
+                ImageFilter::Linear => FILTER_BILINEAR
+            });
+            // keep the edges of the image solid when it is scaled up, instead of fading them out
+            cairo_pattern_set_extend(pattern, EXTEND_PAD);
+            cairo_paint_with_alpha(self.cx, opacity as f64);
+            cairo_restore(self.cx);
+        }
+    }
+
+    fn new_render_target(&self, w: u32, h: u32) -> Result<Self, Box<dyn Error>> {
+        let scale = self.surface.scale();
+        let (pw, ph) = ((w as f32 * scale).ceil() as u32, (h as f32 * scale).ceil() as u32);
+        Ok(CairoRenderContext::from_surface(self.surface.new_similar(pw, ph)?))
+    }
+
+    fn draw_render_target(&mut self, target: &Self, m: &Affine, opacity: f32) {
+        unsafe {
+            let surf = target.surface.surface();
+            cairo_surface_flush(surf);
+            let r = target.bounds();
+            cairo_save(self.cx);
+            cairo_transform(self.cx, &affine_matrix(m));
+            // keep the edges solid when scaled, the same as images
+            cairo_rectangle(self.cx, 0.0, 0.0, r.w as f64, r.h as f64);
+            cairo_clip(self.cx);
+            cairo_set_source_surface(self.cx, surf, 0.0, 0.0);
+            let pattern = cairo_get_source(self.cx);
+            cairo_pattern_set_filter(pattern, FILTER_BILINEAR);
+            cairo_pattern_set_extend(pattern, EXTEND_PAD);
+            cairo_paint_with_alpha(self.cx, opacity as f64);
             cairo_restore(self.cx);
         }
     }
@@ -278,36 +1020,166 @@ impl<S: CairoSurface> RenderContextExt for CairoRenderContext<S> {
         }
     }
 
-    fn new(win: &mut Window) -> Result<Self, Box<Error>> {
+    fn transform(&mut self, m: &Affine) {
         unsafe {
-            let mut surf = S::new(win)?;
-            let cx = cairo_create(surf.surface());
-            let pg = pango_cairo_create_context(cx);
-            Ok(CairoRenderContext {
-                surface: surf, cx, pg
-            })
+            cairo_transform(self.cx, &affine_matrix(m));
+        }
+    }
+
+    fn set_transform(&mut self, m: &Affine) {
+        unsafe {
+            cairo_set_matrix(self.cx, &affine_matrix(m));
+        }
+    }
+
+    fn current_transform(&self) -> Affine {
+        let mut m = MaybeUninit::<Matrix>::uninit();
+        let m = unsafe {
+            cairo_get_matrix(self.cx, m.as_mut_ptr());
+            m.assume_init()
+        };
+        Affine { a: m.xx as f32, b: m.yx as f32, c: m.xy as f32, d: m.yy as f32, e: m.x0 as f32, f: m.y0 as f32 }
+    }
+
+    fn save(&mut self) {
+        let m = self.current_transform();
+        self.saved_transforms.push(m);
+    }
+
+    fn restore(&mut self) {
+        if let Some(m) = self.saved_transforms.pop() {
+            self.set_transform(&m);
+        }
+    }
+
+    fn push_clip_rect(&mut self, rect: Rect) {
+        unsafe {
+            cairo_save(self.cx);
+            cairo_rectangle(self.cx, rect.x as f64, rect.y as f64, rect.w as f64, rect.h as f64);
+            cairo_clip(self.cx);
+        }
+        self.pushed.push(Pushed::Clip);
+    }
+
+    fn push_clip_path(&mut self, path: &Path) {
+        unsafe {
+            cairo_save(self.cx);
+            set_path(self.cx, path);
+            cairo_clip(self.cx);
+        }
+        self.pushed.push(Pushed::Clip);
+    }
+
+    fn pop_clip(&mut self) {
+        if let Some(Pushed::Clip) = self.pushed.last() {
+            unsafe { self.pop_state(); }
+        }
+    }
+
+    fn push_layer(&mut self, opacity: f32, blend: BlendMode) {
+        unsafe {
+            cairo_push_group(self.cx);
         }
+        self.pushed.push(Pushed::Layer(opacity, blend));
+    }
+
+    fn pop_layer(&mut self) {
+        if let Some(i) = self.pushed.iter().rposition(|p| matches!(p, Pushed::Layer(..))) {
+            while self.pushed.len() > i {
+                unsafe { self.pop_state(); }
+            }
+        }
+    }
+
+    fn new(win: &mut Window) -> Result<Self, Box<Error>> {
+        let mut surface = S::new(win)?;
+        surface.set_scale(win.scale_factor() as f32);
+        Ok(CairoRenderContext::from_surface(surface))
+    }
+
+    fn new_offscreen(w: u32, h: u32) -> Result<Self, Box<dyn Error>> {
+        Ok(CairoRenderContext::from_surface(S::new_offscreen(w, h)?))
+    }
+
+    fn new_document(path: &std::path::Path, w: u32, h: u32, format: DocumentFormat) -> Result<Self, Box<dyn Error>> {
+        Ok(CairoRenderContext::from_surface(S::new_document(path, w, h, format)?))
     }
 
     fn bounds(&self) -> Rect { self.surface.bounds() }
 
+    fn read_pixels(&self) -> Result<PixelBuffer, Box<dyn Error>> {
+        unsafe {
+            let surf = self.surface.surface();
+            cairo_surface_flush(surf);
+            let img = cairo_surface_map_to_image(surf, std::ptr::null());
+            let pixels = image_surface_pixels(img);
+            cairo_surface_unmap_image(surf, img);
+            pixels
+        }
+    }
+
+    fn write_png(&self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        let filename = path_to_cstring(path)?;
+        unsafe {
+            let surf = self.surface.surface();
+            cairo_surface_flush(surf);
+            let img = cairo_surface_map_to_image(surf, std::ptr::null());
+            let status = cairo_surface_write_to_png(img, filename.as_ptr());
+            cairo_surface_unmap_image(surf, img);
+            if status != STATUS_SUCCESS {
+                return Err(format!("failed to write PNG file {}", path.display()).into());
+            }
+        }
+        Ok(())
+    }
+
     fn start_paint(&mut self) {
         unsafe {
+            while !self.pushed.is_empty() {
+                self.pop_state();
+            }
             cairo_identity_matrix(self.cx);
+            cairo_reset_clip(self.cx);
         }
+        self.saved_transforms.clear();
+        self.damaged = None;
         self.surface.start_paint()
     }
-    fn end_paint(&mut self) { self.surface.end_paint() }
 
-    fn resize(&mut self, w: u32, h: u32) {
-        self.surface.resize(w,h);
-        unsafe { 
-            cairo_destroy(self.cx);
-            self.cx = cairo_create(self.surface.surface());
-            g_object_unref(transmute(self.pg));
-            self.pg = pango_cairo_create_context(self.cx);
+    fn start_paint_damaged(&mut self, damage: &Damage) {
+        self.start_paint();
+        if let (Some(r), true) = (damage.bounds(), self.surface.keeps_contents()) {
+            // round out to whole pixels so the edges of the damage aren't antialiased
+            let r = r.round_out(self.surface.scale()).intersection(self.bounds()).unwrap_or(Rect::wh(0.0, 0.0));
+            // the clip is set on the base cairo state instead of being pushed, so popping clips
+            // can never remove it
+            unsafe {
+                cairo_rectangle(self.cx, r.x as f64, r.y as f64, r.w as f64, r.h as f64);
+                cairo_clip(self.cx);
+            }
+            self.damaged = Some(r);
         }
     }
 
+    fn damaged_rect(&self) -> Rect {
+        self.damaged.unwrap_or_else(|| self.bounds())
+    }
+    fn end_paint(&mut self) { self.surface.end_paint() }
+
+    fn resize(&mut self, w: u32, h: u32) -> Result<(), Box<dyn Error>> {
+        // cairo can't create surfaces that are too large, and then the old one is kept
+        self.surface.resize(w,h)?;
+        self.recreate_context();
+        Ok(())
+    }
+
     fn pixels_to_points(&self, p: Point) -> Point { self.surface.pixels_to_points(p) }
+
+    fn scale_factor(&self) -> f32 { self.surface.scale() }
+
+    fn set_scale_factor(&mut self, scale: f32) {
+        self.surface.set_scale(scale);
+        // cairo contexts only pick up the device scale of their surface when they're created
+        self.recreate_context();
+    }
 }
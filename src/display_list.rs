@@ -0,0 +1,808 @@
+//! Record drawing into a list of commands that can be replayed, compared and serialized
+//!
+//! A `DisplayList` implements `RenderContextExt`, so drawing code written against the trait can
+//! record into one unchanged. Fonts, text layouts and images are still created by an offscreen
+//! RenderContext that the list owns, so text can be measured while recording.
+//!
+//! With the `serde` feature, lists can be serialized along with the text of the layouts and the
+//! pixels of the images they draw. Layouts and images that were created by some other
+//! RenderContext can be replayed, but are left out when serializing.
+
+use crate::*;
+use std::cell::RefCell;
+
+/// A text layout as it was created, so it can be created again when replaying. The runs come from
+/// an AttributedString, but attributes set on the layout afterwards with `TextLayoutExt` aren't
+/// included
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayoutSource {
+    pub text: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub runs: Vec<TextRun>,
+    pub font: FontDescription, pub width: f32, pub height: f32
+}
+
+/// A single recorded drawing operation, with the arguments of the RenderContextExt method that
+/// recorded it. Text layouts and images are referred to by their index in the DisplayList
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Command {
+    Clear(Color),
+    SetColor(Color),
+    SetBrush(Brush),
+    SetStrokeStyle(StrokeStyle),
+    FillRect(Rect),
+    StrokeRect(Rect, f32),
+    DrawLine(Point, Point, f32),
+    FillPath(Path),
+    StrokePath(Path, f32),
+    FillRoundedRect(Rect, CornerRadii),
+    StrokeRoundedRect(Rect, CornerRadii, f32),
+    FillEllipse(Point, f32, f32),
+    StrokeEllipse(Point, f32, f32, f32),
+    DrawShadow(Shape, Point, f32, Color),
+    DrawText(Rect, String, FontDescription),
+    DrawTextLayout(Point, usize),
+    DrawImage { image: usize, src: Rect, dst: Rect, opacity: f32, filter: ImageFilter },
+    Translate(Point),
+    Transform(Affine),
+    SetTransform(Affine),
+    Save,
+    Restore,
+    PushClipRect(Rect),
+    PushClipPath(Path),
+    PopClip,
+    PushLayer(f32, BlendMode),
+    PopLayer
+}
+
+struct LayoutEntry {
+    id: Option<usize>,
+    handle: Option<TextLayout>,
+    source: Option<LayoutSource>
+}
+
+struct ImageEntry {
+    id: Option<usize>,
+    handle: Option<Image>,
+    pixels: Option<PixelBuffer>
+}
+
+/// A layout or image created through the list, remembered so it can be described when drawn
+struct Created<H, S> {
+    handle: H,
+    source: S,
+    used: bool
+}
+
+/// A list of drawing commands, recorded by using it as a RenderContext
+pub struct DisplayList {
+    rx: RenderContext,
+    size: (u32, u32),
+    scale: f32,
+    commands: Vec<Command>,
+    layouts: Vec<LayoutEntry>,
+    images: Vec<ImageEntry>,
+    created_layouts: RefCell<Vec<Created<TextLayout, LayoutSource>>>,
+    created_images: RefCell<Vec<Created<Image, PixelBuffer>>>,
+    transform: Affine,
+    saved_transforms: Vec<Affine>,
+    damaged: Option<Rect>
+}
+
+impl DisplayList {
+    /// Create an empty list that records drawing into an area `w` by `h` pixels in size
+    pub fn new(w: u32, h: u32) -> Result<DisplayList, Box<dyn Error>> {
+        Ok(DisplayList {
+            rx: RenderContext::new_offscreen(w.max(1), h.max(1))?,
+            size: (w, h),
+            scale: 1.0,
+            commands: Vec::new(),
+            layouts: Vec::new(),
+            images: Vec::new(),
+            created_layouts: RefCell::new(Vec::new()),
+            created_images: RefCell::new(Vec::new()),
+            transform: Affine::identity(),
+            saved_transforms: Vec::new(),
+            damaged: None
+        })
+    }
+
+    /// The commands recorded since the last `start_paint`
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// How the text layout with `index` was created, if it was created by this list
+    pub fn layout_source(&self, index: usize) -> Option<&LayoutSource> {
+        self.layouts.get(index).and_then(|l| l.source.as_ref())
+    }
+
+    /// The pixels of the image with `index`, if it was created by this list
+    pub fn image_pixels(&self, index: usize) -> Option<&PixelBuffer> {
+        self.images.get(index).and_then(|i| i.pixels.as_ref())
+    }
+
+    /// Draw every recorded command into `rx`. This doesn't start or end painting, so a list can be
+    /// replayed as part of a larger frame, and pops anything the commands leave pushed
+    pub fn replay<R: RenderContextExt>(&self, rx: &mut R) {
+        // pops with nothing of the list's own to pop are skipped, so the commands can't remove the
+        // damage clip or anything pushed before replaying
+        let mut nesting = Nesting::default();
+        let keep: Vec<bool> = self.commands.iter().map(|cmd| nesting.apply(cmd)).collect();
+        let close = nesting.close();
+        let commands = self.commands.iter().zip(keep).filter(|(_, keep)| *keep).map(|(cmd, _)| cmd).chain(close.iter());
+        let mut fonts: Vec<(&FontDescription, Font)> = Vec::new();
+        let mut layouts: Vec<Option<TextLayout>> = vec![None; self.layouts.len()];
+        let mut images: Vec<Option<Image>> = vec![None; self.images.len()];
+        if let Some(r) = self.damaged {
+            rx.push_clip_rect(r);
+        }
+        for cmd in commands {
+            match cmd {
+                Command::Clear(col) => rx.clear(*col),
+                Command::SetColor(col) => rx.set_color(*col),
+                Command::SetBrush(brush) => rx.set_brush(brush),
+                Command::SetStrokeStyle(style) => rx.set_stroke_style(style),
+                Command::FillRect(r) => rx.fill_rect(*r),
+                Command::StrokeRect(r, w) => rx.stroke_rect(*r, *w),
+                Command::DrawLine(a, b, w) => rx.draw_line(*a, *b, *w),
+                Command::FillPath(path) => rx.fill_path(path),
+                Command::StrokePath(path, w) => rx.stroke_path(path, *w),
+                Command::FillRoundedRect(r, radii) => rx.fill_rounded_rect(*r, *radii),
+                Command::StrokeRoundedRect(r, radii, w) => rx.stroke_rounded_rect(*r, *radii, *w),
+                Command::FillEllipse(c, erx, ery) => rx.fill_ellipse(*c, *erx, *ery),
+                Command::StrokeEllipse(c, erx, ery, w) => rx.stroke_ellipse(*c, *erx, *ery, *w),
+                Command::DrawShadow(shape, offset, blur, col) => rx.draw_shadow(shape, *offset, *blur, *col),
+                Command::DrawText(r, s, desc) => {
+                    if let Some(f) = replay_font(rx, &mut fonts, desc) {
+                        rx.draw_text(*r, s, &f);
+                    }
+                },
+                Command::DrawTextLayout(p, i) => {
+                    let entry = &self.layouts[*i];
+                    if layouts[*i].is_none() {
+                        // layouts aren't tied to a render target, so the one that was drawn is used
+                        // to keep any attributes set on it
+                        layouts[*i] = entry.handle.clone().or_else(|| entry.source.as_ref().and_then(|src| {
+                            let f = replay_font(rx, &mut fonts, &src.font)?;
+                            if src.runs.is_empty() {
+                                rx.new_text_layout(&src.text, &f, src.width, src.height).ok()
+                            } else {
+                                let text = AttributedString::with_runs(&src.text, &src.runs).ok()?;
+                                rx.new_attributed_text_layout(&text, &f, src.width, src.height).ok()
+                            }
+                        }));
+                    }
+                    if let Some(txl) = &layouts[*i] {
+                        rx.draw_text_layout(*p, txl);
+                    }
+                },
+                Command::DrawImage { image, src, dst, opacity, filter } => {
+                    let entry = &self.images[*image];
+                    if images[*image].is_none() {
+                        // images can belong to a render target, so they are created again if possible
+                        images[*image] = entry.pixels.as_ref().and_then(|px| rx.new_image(px).ok())
+                            .or_else(|| entry.handle.clone());
+                    }
+                    if let Some(img) = &images[*image] {
+                        rx.draw_image(img, *src, *dst, *opacity, *filter);
+                    }
+                },
+                Command::Translate(p) => rx.translate(*p),
+                Command::Transform(m) => rx.transform(m),
+                Command::SetTransform(m) => rx.set_transform(m),
+                Command::Save => rx.save(),
+                Command::Restore => rx.restore(),
+                Command::PushClipRect(r) => rx.push_clip_rect(*r),
+                Command::PushClipPath(path) => rx.push_clip_path(path),
+                Command::PopClip => rx.pop_clip(),
+                Command::PushLayer(opacity, blend) => rx.push_layer(*opacity, *blend),
+                Command::PopLayer => rx.pop_layer()
+            }
+        }
+        if self.damaged.is_some() {
+            rx.pop_clip();
+        }
+    }
+
+    /// Find the area, in points, that could look different when `other` is drawn instead of this
+    /// list. Returns None if both lists draw exactly the same thing.
+    ///
+    /// Commands are compared in order, along with the brush, stroke style, transform, clips and
+    /// layers they are drawn with, so the result can be larger than the pixels that actually changed.
+    /// Text layouts and images created through the lists are compared by their contents, others by
+    /// identity
+    pub fn changed_region(&self, other: &DisplayList) -> Option<Rect> {
+        let a = self.draw_items();
+        let b = other.draw_items();
+        let mut region: Option<Rect> = None;
+        for i in 0..a.len().max(b.len()) {
+            let (x, y) = (a.get(i), b.get(i));
+            if let (Some(x), Some(y)) = (x, y) {
+                if x.state == y.state && self.same_command(x.cmd, other, y.cmd) {
+                    continue;
+                }
+            }
+            for (list, item) in [(self, x), (other, y)].iter() {
+                if let Some(item) = item {
+                    let r = list.item_bounds(item).unwrap_or_else(|| list.bounds());
+                    region = Some(region.map_or(r, |u| u.union(r)));
+                }
+            }
+        }
+        region.and_then(|r| r.intersection(self.bounds().union(other.bounds())))
+    }
+
+    fn same_command(&self, a: &Command, other: &DisplayList, b: &Command) -> bool {
+        match (a, b) {
+            (Command::DrawTextLayout(p, i), Command::DrawTextLayout(q, j)) => {
+                let (x, y) = (&self.layouts[*i], &other.layouts[*j]);
+                p == q && same_entry((x.id, x.source.as_ref()), (y.id, y.source.as_ref()))
+            },
+            (Command::DrawImage { image: i, src, dst, opacity, filter },
+             Command::DrawImage { image: j, src: src2, dst: dst2, opacity: opacity2, filter: filter2 }) => {
+                let (x, y) = (&self.images[*i], &other.images[*j]);
+                (src, dst, opacity, filter) == (src2, dst2, opacity2, filter2)
+                    && same_entry((x.id, x.pixels.as_ref()), (y.id, y.pixels.as_ref()))
+            },
+            _ => a == b
+        }
+    }
+
+    /// Pair every command that draws something with the state it draws with
+    fn draw_items(&self) -> Vec<DrawItem<'_>> {
+        let mut items = Vec::new();
+        let mut state = DrawState {
+            damaged: self.damaged,
+            brush: Brush::Solid(Color::black()),
+            stroke_style: StrokeStyle::default(),
+            transform: Affine::identity(),
+            pushed: Vec::new()
+        };
+        let mut saved = Vec::new();
+        for cmd in &self.commands {
+            match cmd {
+                Command::SetColor(col) => state.brush = Brush::Solid(*col),
+                Command::SetBrush(brush) => state.brush = brush.clone(),
+                Command::SetStrokeStyle(style) => state.stroke_style = style.clone(),
+                Command::Translate(p) => state.transform = Affine::translate(*p).multiply(&state.transform),
+                Command::Transform(m) => state.transform = m.multiply(&state.transform),
+                Command::SetTransform(m) => state.transform = *m,
+                Command::Save => saved.push(state.transform),
+                Command::Restore => if let Some(m) = saved.pop() { state.transform = m },
+                Command::PushClipRect(_) | Command::PushClipPath(_) | Command::PushLayer(..) => state.pushed.push(cmd),
+                Command::PopClip => if let Some(Command::PushClipRect(_)) | Some(Command::PushClipPath(_)) = state.pushed.last() {
+                    state.pushed.pop();
+                },
+                Command::PopLayer => if let Some(i) = state.pushed.iter().rposition(|c| matches!(c, Command::PushLayer(..))) {
+                    state.pushed.truncate(i);
+                },
+                // clearing ignores the brush, but sets it to the clear color
+                Command::Clear(col) => {
+                    items.push(DrawItem { cmd, state: state.clone() });
+                    state.brush = Brush::Solid(*col);
+                },
+                _ => items.push(DrawItem { cmd, state: state.clone() })
+            }
+        }
+        items
+    }
+
+    /// The area in points that an item could draw into, or None if it could be anywhere
+    fn item_bounds(&self, item: &DrawItem) -> Option<Rect> {
+        let style = &item.state.stroke_style;
+        // how far a stroke can reach past its path, allowing for square caps and miter joins
+        let pad = |w: f32| {
+            let k = if style.join == LineJoin::Miter { style.miter_limit.max(std::f32::consts::SQRT_2) } else { std::f32::consts::SQRT_2 };
+            w / 2.0 * k
+        };
+        let r = match item.cmd {
+            Command::FillRect(r) => *r,
+            Command::StrokeRect(r, w) => inflate(*r, pad(*w)),
+            Command::DrawLine(a, b, w) => inflate(points_bounds(&[*a, *b])?, pad(*w)),
+            Command::FillPath(path) => path_bounds(path)?,
+            Command::StrokePath(path, w) => inflate(path_bounds(path)?, pad(*w)),
+            Command::FillRoundedRect(r, _) => *r,
+            Command::StrokeRoundedRect(r, _, w) => inflate(*r, pad(*w)),
+            Command::FillEllipse(c, erx, ery) => Rect::xywh(c.x - erx, c.y - ery, erx * 2.0, ery * 2.0),
+            Command::StrokeEllipse(c, erx, ery, w) => inflate(Rect::xywh(c.x - erx, c.y - ery, erx * 2.0, ery * 2.0), pad(*w)),
+            Command::DrawShadow(shape, offset, blur, _) =>
+                inflate(path_bounds(&shape.to_path())?.offset(*offset), blur.max(0.0) * 1.5),
+            Command::DrawText(r, _, _) => *r,
+            Command::DrawTextLayout(p, i) => self.layouts[*i].handle.as_ref()?.bounds().offset(*p),
+            Command::DrawImage { dst, .. } => *dst,
+            _ => return None
+        };
+        let m = &item.state.transform;
+        let corners = [Point::xy(r.x, r.y), Point::xy(r.x + r.w, r.y), Point::xy(r.x + r.w, r.y + r.h), Point::xy(r.x, r.y + r.h)];
+        let corners = [m.transform_point(corners[0]), m.transform_point(corners[1]),
+                       m.transform_point(corners[2]), m.transform_point(corners[3])];
+        // allow for antialiasing
+        points_bounds(&corners).map(|r| inflate(r, 1.0))
+    }
+
+    fn record_layout(&mut self, txl: &TextLayout) -> usize {
+        let id = imp::layout_id(txl);
+        if let Some(i) = self.layouts.iter().position(|l| l.id == Some(id)) {
+            return i;
+        }
+        let source = self.created_layouts.get_mut().iter_mut().find(|c| imp::layout_id(&c.handle) == id).map(|c| {
+            c.used = true;
+            c.source.clone()
+        });
+        self.layouts.push(LayoutEntry { id: Some(id), handle: Some(txl.clone()), source });
+        self.layouts.len() - 1
+    }
+
+    /// The index of a layout drawn by another list, adding it to this one if it isn't there yet
+    fn nest_layout(&mut self, entry: &LayoutEntry) -> usize {
+        if let Some(i) = entry.id.and_then(|id| self.layouts.iter().position(|l| l.id == Some(id))) {
+            if self.layouts[i].source.is_none() {
+                self.layouts[i].source = entry.source.clone();
+            }
+            return i;
+        }
+        self.layouts.push(LayoutEntry { id: entry.id, handle: entry.handle.clone(), source: entry.source.clone() });
+        self.layouts.len() - 1
+    }
+
+    /// The index of an image drawn by another list, adding it to this one if it isn't there yet
+    fn nest_image(&mut self, entry: &ImageEntry) -> usize {
+        if let Some(i) = entry.id.and_then(|id| self.images.iter().position(|l| l.id == Some(id))) {
+            if self.images[i].pixels.is_none() {
+                self.images[i].pixels = entry.pixels.clone();
+            }
+            return i;
+        }
+        self.images.push(ImageEntry { id: entry.id, handle: entry.handle.clone(), pixels: entry.pixels.clone() });
+        self.images.len() - 1
+    }
+
+    fn record_image(&mut self, img: &Image) -> usize {
+        let id = imp::image_id(img);
+        if let Some(i) = self.images.iter().position(|l| l.id == Some(id)) {
+            return i;
+        }
+        let pixels = self.created_images.get_mut().iter_mut().find(|c| imp::image_id(&c.handle) == id).map(|c| {
+            c.used = true;
+            c.source.clone()
+        });
+        self.images.push(ImageEntry { id: Some(id), handle: Some(img.clone()), pixels });
+        self.images.len() - 1
+    }
+}
+
+fn replay_font<'a, R: RenderContextExt>(rx: &R, fonts: &mut Vec<(&'a FontDescription, Font)>, desc: &'a FontDescription) -> Option<Font> {
+    if let Some((_, f)) = fonts.iter().find(|(d, _)| *d == desc) {
+        return Some(f.clone());
+    }
+    let f = rx.new_font(&desc.name, desc.size, desc.weight, desc.style).ok()?;
+    fonts.push((desc, f.clone()));
+    Some(f)
+}
+
+/// Whether two recorded layouts or images, given by their id and source, draw the same thing. Ones
+/// created through a list are compared by their source, and others by identity. Ones that were
+/// left out when serializing have neither and can't be drawn, so they match each other
+fn same_entry<S: PartialEq>(a: (Option<usize>, Option<&S>), b: (Option<usize>, Option<&S>)) -> bool {
+    match (a.1, b.1) {
+        (Some(x), Some(y)) => x == y,
+        (None, None) => a.0 == b.0,
+        _ => a.0.is_some() && a.0 == b.0
+    }
+}
+
+/// The brush and stroke style that are current after `commands`, starting from those of a new frame
+fn style_after(commands: &[Command]) -> (Brush, StrokeStyle) {
+    let mut brush = Brush::Solid(Color::black());
+    let mut style = StrokeStyle::default();
+    for cmd in commands {
+        match cmd {
+            Command::SetColor(col) | Command::Clear(col) => brush = Brush::Solid(*col),
+            Command::SetBrush(b) => brush = b.clone(),
+            Command::SetStrokeStyle(s) => style = s.clone(),
+            _ => {}
+        }
+    }
+    (brush, style)
+}
+
+/// The clips, layers and saves pushed by a run of commands. Pops that have nothing of the run's own
+/// to pop are skipped, and whatever is left pushed is popped at the end, so the run can't undo
+/// anything pushed around it
+#[derive(Default)]
+struct Nesting {
+    // true for layers and false for clips
+    pushed: Vec<bool>,
+    saves: usize
+}
+
+impl Nesting {
+    /// Track `cmd`, returning false if it should be skipped
+    fn apply(&mut self, cmd: &Command) -> bool {
+        match cmd {
+            Command::PushClipRect(_) | Command::PushClipPath(_) => self.pushed.push(false),
+            Command::PushLayer(..) => self.pushed.push(true),
+            Command::PopClip => if self.pushed.last() == Some(&false) {
+                self.pushed.pop();
+            } else {
+                return false;
+            },
+            Command::PopLayer => match self.pushed.iter().rposition(|layer| *layer) {
+                Some(i) => self.pushed.truncate(i),
+                None => return false
+            },
+            Command::Save => self.saves += 1,
+            Command::Restore => match self.saves.checked_sub(1) {
+                Some(n) => self.saves = n,
+                None => return false
+            },
+            _ => {}
+        }
+        true
+    }
+
+    /// The commands that pop everything left pushed
+    fn close(&self) -> Vec<Command> {
+        let mut cmds: Vec<Command> = self.pushed.iter().rev()
+            .map(|&layer| if layer { Command::PopLayer } else { Command::PopClip })
+            .collect();
+        cmds.extend((0..self.saves).map(|_| Command::Restore));
+        cmds
+    }
+}
+
+#[derive(Clone,PartialEq)]
+struct DrawState<'a> {
+    damaged: Option<Rect>,
+    brush: Brush,
+    stroke_style: StrokeStyle,
+    transform: Affine,
+    pushed: Vec<&'a Command>
+}
+
+struct DrawItem<'a> {
+    cmd: &'a Command,
+    state: DrawState<'a>
+}
+
+fn points_bounds(points: &[Point]) -> Option<Rect> {
+    let first = points.first()?;
+    let (mut x0, mut y0, mut x1, mut y1) = (first.x, first.y, first.x, first.y);
+    for p in points {
+        x0 = x0.min(p.x);
+        y0 = y0.min(p.y);
+        x1 = x1.max(p.x);
+        y1 = y1.max(p.y);
+    }
+    Some(Rect::xywh(x0, y0, x1 - x0, y1 - y0))
+}
+
+/// A rectangle containing the path. Control points are included, so it may be larger than the path
+fn path_bounds(path: &Path) -> Option<Rect> {
+    let mut points = Vec::new();
+    for seg in path.segments() {
+        match *seg {
+            PathSegment::MoveTo(p) | PathSegment::LineTo(p) => points.push(p),
+            PathSegment::QuadTo(c, p) => points.extend_from_slice(&[c, p]),
+            PathSegment::CubicTo(c1, c2, p) => points.extend_from_slice(&[c1, c2, p]),
+            PathSegment::Arc { center, radius, .. } => points.extend_from_slice(&[
+                Point::xy(center.x - radius, center.y - radius), Point::xy(center.x + radius, center.y + radius)
+            ]),
+            PathSegment::Close => {}
+        }
+    }
+    points_bounds(&points)
+}
+
+fn inflate(r: Rect, d: f32) -> Rect {
+    Rect::xywh(r.x - d, r.y - d, r.w + d * 2.0, r.h + d * 2.0)
+}
+
+impl RenderContextExt for DisplayList {
+    fn new(win: &mut winit::window::Window) -> Result<Self, Box<dyn Error>> {
+        let size = win.inner_size();
+        let mut list = DisplayList::new(size.width, size.height)?;
+        list.set_scale_factor(win.scale_factor() as f32);
+        Ok(list)
+    }
+
+    fn new_offscreen(w: u32, h: u32) -> Result<Self, Box<dyn Error>> {
+        DisplayList::new(w, h)
+    }
+
+    fn new_document(_path: &std::path::Path, _w: u32, _h: u32, _format: DocumentFormat) -> Result<Self, Box<dyn Error>> {
+        Err("a DisplayList can't render into a document, replay it into a document RenderContext instead".into())
+    }
+
+    fn new_font(&self, name: &str, size: f32, weight: FontWeight, style: FontStyle) -> Result<Font, Box<dyn Error>> {
+        self.rx.new_font(name, size, weight, style)
+    }
+
+    fn new_text_layout(&self, text: &str, f: &Font, width: f32, height: f32) -> Result<TextLayout, Box<dyn Error>> {
+        let txl = self.rx.new_text_layout(text, f, width, height)?;
+        let source = LayoutSource { text: text.to_string(), runs: Vec::new(), font: f.description(), width, height };
+        self.created_layouts.borrow_mut().push(Created { handle: txl.clone(), source, used: true });
+        Ok(txl)
+    }
+
+    fn new_attributed_text_layout(&self, text: &AttributedString, f: &Font, width: f32, height: f32) -> Result<TextLayout, Box<dyn Error>> {
+        let txl = self.rx.new_attributed_text_layout(text, f, width, height)?;
+        let source = LayoutSource { text: text.text().to_string(), runs: text.runs().to_vec(), font: f.description(), width, height };
+        self.created_layouts.borrow_mut().push(Created { handle: txl.clone(), source, used: true });
+        Ok(txl)
+    }
+
+    fn clear(&mut self, col: Color) { self.commands.push(Command::Clear(col)); }
+    fn set_color(&mut self, col: Color) { self.commands.push(Command::SetColor(col)); }
+    fn set_brush(&mut self, brush: &Brush) { self.commands.push(Command::SetBrush(brush.clone())); }
+    fn stroke_rect(&mut self, rect: Rect, stroke_width: f32) { self.commands.push(Command::StrokeRect(rect, stroke_width)); }
+    fn fill_rect(&mut self, rect: Rect) { self.commands.push(Command::FillRect(rect)); }
+    fn draw_line(&mut self, a: Point, b: Point, stroke_width: f32) { self.commands.push(Command::DrawLine(a, b, stroke_width)); }
+    fn set_stroke_style(&mut self, style: &StrokeStyle) { self.commands.push(Command::SetStrokeStyle(style.clone())); }
+    fn fill_path(&mut self, path: &Path) { self.commands.push(Command::FillPath(path.clone())); }
+    fn stroke_path(&mut self, path: &Path, stroke_width: f32) { self.commands.push(Command::StrokePath(path.clone(), stroke_width)); }
+
+    fn fill_rounded_rect(&mut self, rect: Rect, radii: CornerRadii) {
+        self.commands.push(Command::FillRoundedRect(rect, radii));
+    }
+
+    fn stroke_rounded_rect(&mut self, rect: Rect, radii: CornerRadii, stroke_width: f32) {
+        self.commands.push(Command::StrokeRoundedRect(rect, radii, stroke_width));
+    }
+
+    fn fill_ellipse(&mut self, center: Point, rx: f32, ry: f32) {
+        self.commands.push(Command::FillEllipse(center, rx, ry));
+    }
+
+    fn stroke_ellipse(&mut self, center: Point, rx: f32, ry: f32, stroke_width: f32) {
+        self.commands.push(Command::StrokeEllipse(center, rx, ry, stroke_width));
+    }
+
+    fn draw_shadow(&mut self, shape: &Shape, offset: Point, blur_radius: f32, color: Color) {
+        self.commands.push(Command::DrawShadow(shape.clone(), offset, blur_radius, color));
+    }
+
+    fn draw_text(&mut self, rect: Rect, s: &str, f: &Font) {
+        self.commands.push(Command::DrawText(rect, s.to_string(), f.description()));
+    }
+
+    fn draw_text_layout(&mut self, p: Point, txl: &TextLayout) {
+        let i = self.record_layout(txl);
+        self.commands.push(Command::DrawTextLayout(p, i));
+    }
+
+    fn new_image(&self, pixels: &PixelBuffer) -> Result<Image, Box<dyn Error>> {
+        let img = self.rx.new_image(pixels)?;
+        self.created_images.borrow_mut().push(Created { handle: img.clone(), source: pixels.clone(), used: true });
+        Ok(img)
+    }
+
+    fn draw_image(&mut self, img: &Image, src: Rect, dst: Rect, opacity: f32, filter: ImageFilter) {
+        let image = self.record_image(img);
+        self.commands.push(Command::DrawImage { image, src, dst, opacity, filter });
+    }
+
+    fn new_render_target(&self, w: u32, h: u32) -> Result<DisplayList, Box<dyn Error>> {
+        let s = self.scale;
+        let mut list = DisplayList::new((w as f32 * s).ceil() as u32, (h as f32 * s).ceil() as u32)?;
+        list.set_scale_factor(s);
+        Ok(list)
+    }
+
+    /// Record the commands `target` last painted, so they are still drawn as vectors when the list
+    /// is replayed. They are drawn through a layer with `opacity`, transformed by `m` and clipped to
+    /// the bounds of the target
+    fn draw_render_target(&mut self, target: &DisplayList, m: &Affine, opacity: f32) {
+        let (brush, style) = style_after(&self.commands);
+        self.save();
+        self.transform(m);
+        let outer = self.transform;
+        self.push_layer(opacity, BlendMode::Normal);
+        self.push_clip_rect(target.bounds());
+        if let Some(r) = target.damaged {
+            self.push_clip_rect(r);
+        }
+        // the target's commands start from the brush and stroke style of a new frame
+        if brush != Brush::Solid(Color::black()) {
+            self.set_color(Color::black());
+        }
+        if style != StrokeStyle::default() {
+            self.set_stroke_style(&StrokeStyle::default());
+        }
+        let mut nesting = Nesting::default();
+        for cmd in &target.commands {
+            if !nesting.apply(cmd) {
+                continue;
+            }
+            let cmd = match cmd {
+                Command::DrawTextLayout(p, i) => Command::DrawTextLayout(*p, self.nest_layout(&target.layouts[*i])),
+                Command::DrawImage { image, src, dst, opacity, filter } => Command::DrawImage {
+                    image: self.nest_image(&target.images[*image]), src: *src, dst: *dst, opacity: *opacity, filter: *filter
+                },
+                // transforms set by the target are relative to where it is drawn
+                Command::SetTransform(t) => Command::SetTransform(t.multiply(&outer)),
+                cmd => cmd.clone()
+            };
+            self.commands.push(cmd);
+        }
+        self.commands.extend(nesting.close());
+        self.pop_layer();
+        self.restore();
+        // drawing a target leaves the brush and stroke style alone
+        let (after_brush, after_style) = style_after(&self.commands);
+        if after_brush != brush {
+            self.set_brush(&brush);
+        }
+        if after_style != style {
+            self.set_stroke_style(&style);
+        }
+    }
+
+    fn translate(&mut self, p: Point) {
+        self.transform = Affine::translate(p).multiply(&self.transform);
+        self.commands.push(Command::Translate(p));
+    }
+
+    fn transform(&mut self, m: &Affine) {
+        self.transform = m.multiply(&self.transform);
+        self.commands.push(Command::Transform(*m));
+    }
+
+    fn set_transform(&mut self, m: &Affine) {
+        self.transform = *m;
+        self.commands.push(Command::SetTransform(*m));
+    }
+
+    fn current_transform(&self) -> Affine {
+        self.transform
+    }
+
+    fn save(&mut self) {
+        self.saved_transforms.push(self.transform);
+        self.commands.push(Command::Save);
+    }
+
+    fn restore(&mut self) {
+        if let Some(m) = self.saved_transforms.pop() {
+            self.transform = m;
+        }
+        self.commands.push(Command::Restore);
+    }
+
+    fn push_clip_rect(&mut self, rect: Rect) { self.commands.push(Command::PushClipRect(rect)); }
+    fn push_clip_path(&mut self, path: &Path) { self.commands.push(Command::PushClipPath(path.clone())); }
+    fn pop_clip(&mut self) { self.commands.push(Command::PopClip); }
+    fn push_layer(&mut self, opacity: f32, blend: BlendMode) { self.commands.push(Command::PushLayer(opacity, blend)); }
+    fn pop_layer(&mut self) { self.commands.push(Command::PopLayer); }
+
+    fn bounds(&self) -> Rect {
+        Rect::wh(self.size.0 as f32 / self.scale, self.size.1 as f32 / self.scale)
+    }
+
+    fn read_pixels(&self) -> Result<PixelBuffer, Box<dyn Error>> {
+        let mut rx = RenderContext::new_offscreen(self.size.0, self.size.1)?;
+        rx.set_scale_factor(self.scale);
+        rx.start_paint();
+        self.replay(&mut rx);
+        rx.end_paint();
+        rx.read_pixels()
+    }
+
+    fn write_png(&self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        self.read_pixels()?.write_png(path)
+    }
+
+    /// Forget the commands recorded for the last frame. Layouts and images created through the list
+    /// are remembered until a frame passes without them being drawn
+    fn start_paint(&mut self) {
+        self.commands.clear();
+        self.layouts.clear();
+        self.images.clear();
+        self.created_layouts.get_mut().retain(|c| c.used);
+        self.created_images.get_mut().retain(|c| c.used);
+        for c in self.created_layouts.get_mut().iter_mut() { c.used = false; }
+        for c in self.created_images.get_mut().iter_mut() { c.used = false; }
+        self.transform = Affine::identity();
+        self.saved_transforms.clear();
+        self.damaged = None;
+    }
+
+    /// Start recording a frame that only redraws the bounds of `damage`. The clip to it is kept
+    /// apart from the recorded commands, and pushed around them when they are replayed
+    fn start_paint_damaged(&mut self, damage: &Damage) {
+        self.start_paint();
+        if let Some(r) = damage.bounds() {
+            let r = r.round_out(self.scale).intersection(self.bounds()).unwrap_or(Rect::wh(0.0, 0.0));
+            self.damaged = Some(r);
+        }
+    }
+
+    fn damaged_rect(&self) -> Rect {
+        self.damaged.unwrap_or_else(|| self.bounds())
+    }
+
+    fn end_paint(&mut self) {}
+
+    fn resize(&mut self, w: u32, h: u32) -> Result<(), Box<dyn Error>> {
+        self.rx.resize(w.max(1), h.max(1))?;
+        self.size = (w, h);
+        Ok(())
+    }
+
+    fn pixels_to_points(&self, p: Point) -> Point { self.rx.pixels_to_points(p) }
+
+    fn scale_factor(&self) -> f32 { self.scale }
+
+    fn set_scale_factor(&mut self, scale: f32) {
+        self.scale = scale;
+        self.rx.set_scale_factor(scale);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SerializedList<'a> {
+    width: u32, height: u32,
+    scale: f32,
+    damaged: Option<Rect>,
+    commands: &'a [Command],
+    layouts: Vec<Option<&'a LayoutSource>>,
+    images: Vec<Option<&'a PixelBuffer>>
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct DeserializedList {
+    width: u32, height: u32,
+    #[serde(default = "default_scale")]
+    scale: f32,
+    #[serde(default)]
+    damaged: Option<Rect>,
+    commands: Vec<Command>,
+    layouts: Vec<Option<LayoutSource>>,
+    images: Vec<Option<PixelBuffer>>
+}
+
+#[cfg(feature = "serde")]
+fn default_scale() -> f32 { 1.0 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DisplayList {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedList {
+            width: self.size.0, height: self.size.1,
+            scale: self.scale,
+            damaged: self.damaged,
+            commands: &self.commands,
+            layouts: self.layouts.iter().map(|l| l.source.as_ref()).collect(),
+            images: self.images.iter().map(|i| i.pixels.as_ref()).collect()
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DisplayList {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<DisplayList, D::Error> {
+        use serde::de::Error as _;
+        let s = DeserializedList::deserialize(deserializer)?;
+        for cmd in &s.commands {
+            match cmd {
+                Command::DrawTextLayout(_, i) if *i >= s.layouts.len() =>
+                    return Err(D::Error::custom(format!("text layout {} is out of range", i))),
+                Command::DrawImage { image, .. } if *image >= s.images.len() =>
+                    return Err(D::Error::custom(format!("image {} is out of range", image))),
+                _ => {}
+            }
+        }
+        let mut list = DisplayList::new(s.width, s.height).map_err(D::Error::custom)?;
+        list.set_scale_factor(s.scale);
+        list.damaged = s.damaged;
+        list.commands = s.commands;
+        list.layouts = s.layouts.into_iter().map(|source| LayoutEntry { id: None, handle: None, source }).collect();
+        list.images = s.images.into_iter().map(|pixels| ImageEntry { id: None, handle: None, pixels }).collect();
+        Ok(list)
+    }
+}
@@ -0,0 +1,287 @@
+//! Drive an App with a scripted sequence of events, without a window
+//!
+//! A `Driver` runs an App against an offscreen RenderContext and delivers events to it exactly
+//! like `runic::start` would, so tests can replay input and inspect the App and its frames after
+//! each step.
+//!
+//! Windows the App opens are offscreen RenderContexts too, and events can be sent to any of them
+//! with `send_to`.
+//!
+//! Time is simulated too. The Driver's clock only moves when `advance` is called, which wakes up
+//! at every time the event loop would have in between, firing the App's timers and painting its
+//! animation frames exactly like `runic::start` does.
+
+use crate::*;
+use winit::event::DeviceId;
+use std::time::{Duration, Instant};
+
+/// Runs an App against an offscreen RenderContext, feeding it events one step at a time
+pub struct Driver<AppT: App> {
+    pub app: AppT,
+    /// The RenderContext of the main window
+    pub rx: RenderContext,
+    main_open: bool,
+    // the windows the App has opened, besides the main one
+    windows: Vec<(WindowId, RenderContext)>,
+    next_id: u64,
+    flow: ControlFlowOpts,
+    frames: usize,
+    now: Instant,
+    clock: FrameClock,
+}
+
+fn device_id() -> DeviceId {
+    // the id is only ever compared by the App, it is never passed back into winit
+    unsafe { DeviceId::dummy() }
+}
+
+impl<AppT: App> Driver<AppT> {
+    /// Initialize `AppT` with an offscreen RenderContext `w` by `h` pixels in size and paint its
+    /// first frame, as a new window would
+    pub fn new(w: u32, h: u32) -> Result<Driver<AppT>, Box<dyn Error>> {
+        let mut rx = RenderContext::new_offscreen(w, h)?;
+        let app = AppT::init(&mut rx);
+        let mut driver = Driver {
+            app, rx, main_open: true, windows: Vec::new(), next_id: 1,
+            flow: ControlFlowOpts::Wait, frames: 0, now: Instant::now(), clock: FrameClock::default()
+        };
+        let mut all = Damage::new();
+        all.add_all();
+        driver.wake(vec![(WindowId::MAIN, all)]);
+        Ok(driver)
+    }
+
+    /// Paint a frame of the main window, regardless of whether the App asked for one
+    pub fn paint(&mut self) -> &mut Self {
+        let mut damage = Damage::new();
+        damage.add_all();
+        self.paint_damaged(&damage)
+    }
+
+    /// Paint a frame of the main window that only redraws the parts in `damage`, as `runic::start`
+    /// does after events that report damage
+    pub fn paint_damaged(&mut self, damage: &Damage) -> &mut Self {
+        self.paint_window(WindowId::MAIN, damage)
+    }
+
+    fn paint_window(&mut self, window: WindowId, damage: &Damage) -> &mut Self {
+        let rx = if window == WindowId::MAIN {
+            self.frames += 1;
+            &mut self.rx
+        } else {
+            match self.windows.iter_mut().find(|(id, _)| *id == window) {
+                Some((_, rx)) => rx,
+                None => return self
+            }
+        };
+        rx.start_paint_damaged(damage);
+        self.app.paint_window(window, rx);
+        rx.end_paint();
+        self
+    }
+
+    /// Deliver an event for the main window to the App, then paint a frame if the App reported any
+    /// damage or the event otherwise invalidates the window. Once the App has exited, events are
+    /// ignored
+    pub fn send(&mut self, e: Event<'_>) -> &mut Self {
+        self.send_to(WindowId::MAIN, e)
+    }
+
+    /// Deliver an event for `window` to the App, like `send`. Windows the App opens while handling
+    /// it are created offscreen and painted, and events for windows that aren't open are ignored
+    pub fn send_to(&mut self, window: WindowId, e: Event<'_>) -> &mut Self {
+        if self.exited() || !self.window_ids().contains(&window) {
+            return self;
+        }
+        let mut damage = Damage::new();
+        self.flow = ControlFlowOpts::Wait;
+        let ids = self.window_ids();
+        let (size, scale) = (self.rx.bounds(), self.rx.scale_factor());
+        let mut opened = Vec::new();
+        let mut open_window = |id, opts: WindowOptions| -> Result<(), Box<dyn Error>> {
+            let main_size = dpi::LogicalSize::new(size.w, size.h).to_physical(scale as f64);
+            let size = opts.window.inner_size.map_or(main_size, |s| s.to_physical(scale as f64));
+            let mut rx = RenderContext::new_offscreen(size.width, size.height)?;
+            rx.set_scale_factor(scale);
+            opened.push((id, rx));
+            Ok(())
+        };
+        let mut windows = Windows::new(&mut open_window, &mut self.next_id, ids);
+        let rx = if window == WindowId::MAIN {
+            &mut self.rx
+        } else {
+            &mut self.windows.iter_mut().find(|(id, _)| *id == window).expect("window is open").1
+        };
+        if deliver_event(&mut self.app, window, rx, e, &mut self.flow, &mut damage, &mut windows) {
+            damage.add_all();
+        }
+        let closed = windows.closed;
+        self.main_open &= !closed.contains(&WindowId::MAIN);
+        self.windows.retain(|(id, _)| !closed.contains(id));
+        let mut pending = vec![(window, damage)];
+        for (id, rx) in opened {
+            self.windows.push((id, rx));
+            // new windows are painted in full
+            let mut all = Damage::new();
+            all.add_all();
+            pending.push((id, all));
+        }
+        self.wake(pending);
+        self
+    }
+
+    /// Move the clock forward by `dt`. The Driver wakes up at each time in between that the event
+    /// loop would, when a timer is due or an animation frame should be painted, so animations are
+    /// ticked about 60 times a second however far the clock is moved at once
+    pub fn advance(&mut self, dt: Duration) -> &mut Self {
+        let end = self.now + dt;
+        while let Some(t) = self.clock.next_wake(&self.app).filter(|t| *t <= end) {
+            if self.exited() {
+                break;
+            }
+            self.now = self.now.max(t);
+            self.wake(Vec::new());
+            // a timer that firing doesn't move on would wake the Driver forever
+            if self.clock.next_wake(&self.app) == Some(t) {
+                break;
+            }
+        }
+        self.now = end;
+        self
+    }
+
+    /// The time on the Driver's clock, which starts at the time the Driver was created
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    /// Update the App's timers and animation at the current time, as the event loop does once it
+    /// has delivered events, then paint the windows that have damage. `pending` is the damage
+    /// events left in each window
+    fn wake(&mut self, pending: Vec<(WindowId, Damage)>) {
+        let mut damage = Damage::new();
+        let frame = self.clock.update(&mut self.app, self.now, &mut damage);
+        if self.exited() {
+            return;
+        }
+        for id in self.window_ids() {
+            let mut d = Damage::new();
+            // animation frames are painted in every window
+            if frame {
+                d.add_all();
+            } else if id == WindowId::MAIN {
+                d.merge(&damage);
+            }
+            for (_, p) in pending.iter().filter(|(w, _)| *w == id) {
+                d.merge(p);
+            }
+            if !d.is_empty() {
+                self.paint_window(id, &d);
+            }
+        }
+    }
+
+    /// Move the cursor to `p`, in pixels
+    pub fn cursor_moved(&mut self, p: Point) -> &mut Self {
+        #[allow(deprecated)]
+        self.send(Event::CursorMoved {
+            device_id: device_id(),
+            position: dpi::PhysicalPosition { x: p.x as f64, y: p.y as f64 },
+            modifiers: ModifiersState::empty()
+        })
+    }
+
+    /// Press or release a mouse button
+    pub fn mouse_input(&mut self, button: MouseButton, state: ElementState) -> &mut Self {
+        #[allow(deprecated)]
+        self.send(Event::MouseInput { device_id: device_id(), state, button, modifiers: ModifiersState::empty() })
+    }
+
+    /// Press or release a key
+    pub fn keyboard_input(&mut self, key: VirtualKeyCode, state: ElementState) -> &mut Self {
+        #[allow(deprecated)]
+        self.send(Event::KeyboardInput {
+            device_id: device_id(),
+            input: KeyboardInput {
+                scancode: 0, state, virtual_keycode: Some(key),
+                modifiers: ModifiersState::empty()
+            },
+            is_synthetic: false
+        })
+    }
+
+    /// Press and then release a key
+    pub fn key_press(&mut self, key: VirtualKeyCode) -> &mut Self {
+        self.keyboard_input(key, ElementState::Pressed).keyboard_input(key, ElementState::Released)
+    }
+
+    /// Type a character
+    pub fn received_character(&mut self, c: char) -> &mut Self {
+        self.send(Event::ReceivedCharacter(c))
+    }
+
+    /// Resize the window to `w` by `h` pixels
+    pub fn resize(&mut self, w: u32, h: u32) -> &mut Self {
+        self.send(Event::Resized(dpi::PhysicalSize::new(w, h)))
+    }
+
+    /// Move the window to a display with `scale` pixels per point, keeping its size in points. The
+    /// window is resized to whatever size the App leaves in `new_inner_size`
+    pub fn scale_factor_changed(&mut self, scale: f32) -> &mut Self {
+        let b = self.rx.bounds();
+        let mut new_inner_size = dpi::LogicalSize::new(b.w, b.h).to_physical(scale as f64);
+        self.send(Event::ScaleFactorChanged { scale_factor: scale as f64, new_inner_size: &mut new_inner_size })
+    }
+
+    /// Ask the App to close, as if the user closed the window
+    pub fn close(&mut self) -> &mut Self {
+        self.send(Event::CloseRequested)
+    }
+
+    /// The control flow the App requested while handling the last event
+    pub fn control_flow(&self) -> ControlFlowOpts {
+        self.flow
+    }
+
+    /// Returns true if the App has asked the event loop to exit
+    pub fn exited(&self) -> bool {
+        self.flow == ControlFlowOpts::Exit
+    }
+
+    /// The number of frames that have been painted in the main window so far
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    /// The ids of the windows that are open, starting with the main window if it is
+    pub fn window_ids(&self) -> Vec<WindowId> {
+        let main = if self.main_open { Some(WindowId::MAIN) } else { None };
+        main.into_iter().chain(self.windows.iter().map(|(id, _)| *id)).collect()
+    }
+
+    /// The RenderContext of one of the windows that the App opened
+    pub fn window(&self, id: WindowId) -> Option<&RenderContext> {
+        self.windows.iter().find(|(w, _)| *w == id).map(|(_, rx)| rx)
+    }
+
+    /// Read back the most recently painted frame
+    pub fn frame(&self) -> PixelBuffer {
+        self.rx.read_pixels().expect("read frame pixels")
+    }
+}
+
+impl<AppT: UserEventApp> Driver<AppT> {
+    /// Deliver an event to the App as if it was sent through an `EventProxy`, then paint a frame
+    /// if the App set `should_redraw`. There's no event loop to make a proxy for, so
+    /// `UserEventApp::event_proxy` is never called by a Driver
+    pub fn user_event(&mut self, e: AppT::UserEvent) -> &mut Self {
+        if self.exited() {
+            return self;
+        }
+        let mut damage = Damage::new();
+        self.flow = ControlFlowOpts::Wait;
+        deliver_user_event(&mut self.app, e, &mut self.flow, &mut damage);
+        self.wake(vec![(WindowId::MAIN, damage)]);
+        self
+    }
+}
@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::ops::Range;
+use std::time::{Duration, Instant};
 
 #[cfg(target_os="windows")]
 mod windows;
@@ -11,6 +12,13 @@ mod unix;
 #[cfg(any(target_os="macos", target_os="linux"))]
 mod cairo_context;
 
+pub mod snapshot;
+pub mod driver;
+pub mod display_list;
+mod attributed_string;
+
+pub use attributed_string::{AttributedString, TextAttribute, TextRun};
+
 #[cfg(target_os="windows")]
 use windows as imp;
 #[cfg(target_os="macos")]
@@ -18,7 +26,8 @@ use macos as imp;
 #[cfg(target_os="linux")]
 use unix as imp;
 
-#[derive(Copy,Clone,Debug)]
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point { pub x: f32, pub y: f32 }
 
 impl Point {
@@ -104,7 +113,8 @@ impl From<(f64, f64)> for Point {
         Point {x: x as f32, y: y as f32}
     }
 }
-#[derive(Copy,Clone,Debug)]
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     pub x: f32, pub y: f32, pub w: f32, pub h: f32
 }
@@ -135,9 +145,107 @@ impl Rect {
     pub fn contains(&self, p: Point) -> bool {
         p.x >= self.x && p.y >= self.y && p.x <= self.x+self.w && p.y <= self.y+self.h
     }
+    /// Returns true if this rectangle has no area
+    pub fn is_empty(&self) -> bool {
+        self.w <= 0.0 || self.h <= 0.0
+    }
+    /// Returns the smallest rectangle containing both this rectangle and `other`
+    pub fn union(&self, other: Rect) -> Rect {
+        let (x0, y0) = (self.x.min(other.x), self.y.min(other.y));
+        let (x1, y1) = ((self.x+self.w).max(other.x+other.w), (self.y+self.h).max(other.y+other.h));
+        Rect { x: x0, y: y0, w: x1-x0, h: y1-y0 }
+    }
+    /// Returns the smallest rectangle containing this one with its edges on whole pixels, when there
+    /// are `scale` pixels per point
+    pub fn round_out(&self, scale: f32) -> Rect {
+        let (x0, y0) = ((self.x*scale).floor()/scale, (self.y*scale).floor()/scale);
+        let (x1, y1) = (((self.x+self.w)*scale).ceil()/scale, ((self.y+self.h)*scale).ceil()/scale);
+        Rect { x: x0, y: y0, w: x1-x0, h: y1-y0 }
+    }
+    /// Returns the area covered by both this rectangle and `other`, if there is any
+    pub fn intersection(&self, other: Rect) -> Option<Rect> {
+        let (x0, y0) = (self.x.max(other.x), self.y.max(other.y));
+        let (x1, y1) = ((self.x+self.w).min(other.x+other.w), (self.y+self.h).min(other.y+other.h));
+        if x1 <= x0 || y1 <= y0 { None } else { Some(Rect { x: x0, y: y0, w: x1-x0, h: y1-y0 }) }
+    }
+}
+
+/// A 2D affine transformation, which maps a point (x, y) to (a*x + c*y + e, b*x + d*y + f)
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Affine {
+    pub a: f32, pub b: f32, pub c: f32, pub d: f32, pub e: f32, pub f: f32
 }
 
-#[derive(Copy,Clone,Debug)]
+impl Affine {
+    /// The transformation that leaves every point where it is
+    pub fn identity() -> Affine {
+        Affine { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 }
+    }
+
+    /// Move every point by `p`
+    pub fn translate(p: Point) -> Affine {
+        Affine { e: p.x, f: p.y, ..Affine::identity() }
+    }
+
+    /// Scale around the origin by `sx` horizontally and `sy` vertically
+    pub fn scale(sx: f32, sy: f32) -> Affine {
+        Affine { a: sx, d: sy, ..Affine::identity() }
+    }
+
+    /// Rotate around the origin by `angle` radians. With y pointing down this is clockwise
+    pub fn rotate(angle: f32) -> Affine {
+        let (sin, cos) = angle.sin_cos();
+        Affine { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// Skew by `x_angle` radians along the x axis and `y_angle` radians along the y axis
+    pub fn skew(x_angle: f32, y_angle: f32) -> Affine {
+        Affine { b: y_angle.tan(), c: x_angle.tan(), ..Affine::identity() }
+    }
+
+    /// The transformation that applies this one and then `other`
+    pub fn multiply(&self, other: &Affine) -> Affine {
+        Affine {
+            a: self.a*other.a + self.b*other.c,
+            b: self.a*other.b + self.b*other.d,
+            c: self.c*other.a + self.d*other.c,
+            d: self.c*other.b + self.d*other.d,
+            e: self.e*other.a + self.f*other.c + other.e,
+            f: self.e*other.b + self.f*other.d + other.f
+        }
+    }
+
+    /// The transformation that undoes this one, or None if it collapses points onto a line
+    pub fn invert(&self) -> Option<Affine> {
+        let det = self.a*self.d - self.b*self.c;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        Some(Affine {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c*self.f - self.d*self.e) / det,
+            f: (self.b*self.e - self.a*self.f) / det
+        })
+    }
+
+    /// Apply this transformation to `p`
+    pub fn transform_point(&self, p: Point) -> Point {
+        Point { x: self.a*p.x + self.c*p.y + self.e, y: self.b*p.x + self.d*p.y + self.f }
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Affine {
+        Affine::identity()
+    }
+}
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     r: f32, g: f32, b: f32, a: f32
 }
@@ -172,17 +280,510 @@ impl Color {
     }
 }
 
+/// How a gradient is continued outside of the range between its first and last stops
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExtendMode {
+    /// The color of the nearest end stop is used
+    Pad,
+    /// The gradient starts over again
+    Repeat,
+    /// The gradient is repeated, mirrored each time
+    Reflect
+}
+
+/// A color at a position along a gradient, from 0 at the start to 1 at the end
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GradientStop {
+    pub offset: f32, pub color: Color
+}
+
+impl GradientStop {
+    pub fn new(offset: f32, color: Color) -> GradientStop {
+        GradientStop { offset, color }
+    }
+}
+
+/// The paint used by fill and stroke operations. Gradients are positioned in the same coordinates
+/// as the shapes they paint, and their stops should be given in order of increasing offset
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Brush {
+    /// A single flat color
+    Solid(Color),
+    /// Colors that change along the line from `start` to `end`
+    LinearGradient { start: Point, end: Point, stops: Vec<GradientStop>, extend: ExtendMode },
+    /// Colors that change with the distance from `center`, reaching the last stop at `radius`
+    RadialGradient { center: Point, radius: f32, stops: Vec<GradientStop>, extend: ExtendMode }
+}
+
+impl Brush {
+    /// A gradient from `start` to `end` with the given stops, padded with the end colors
+    pub fn linear(start: Point, end: Point, stops: &[GradientStop]) -> Brush {
+        Brush::LinearGradient { start, end, stops: stops.to_vec(), extend: ExtendMode::Pad }
+    }
+
+    /// A gradient out from `center` to `radius` with the given stops, padded with the end colors
+    pub fn radial(center: Point, radius: f32, stops: &[GradientStop]) -> Brush {
+        Brush::RadialGradient { center, radius, stops: stops.to_vec(), extend: ExtendMode::Pad }
+    }
+}
+
+impl From<Color> for Brush {
+    fn from(col: Color) -> Brush {
+        Brush::Solid(col)
+    }
+}
+
+/// The radius of each corner of a rounded rectangle
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CornerRadii {
+    pub top_left: f32, pub top_right: f32, pub bottom_right: f32, pub bottom_left: f32
+}
+
+impl CornerRadii {
+    /// Use the same radius for every corner
+    pub fn all(r: f32) -> CornerRadii {
+        CornerRadii { top_left: r, top_right: r, bottom_right: r, bottom_left: r }
+    }
+
+    /// Returns true if every corner has the same radius
+    pub fn is_uniform(&self) -> bool {
+        self.top_left == self.top_right && self.top_left == self.bottom_right && self.top_left == self.bottom_left
+    }
+}
+
+impl From<f32> for CornerRadii {
+    fn from(r: f32) -> CornerRadii {
+        CornerRadii::all(r)
+    }
+}
+
+/// The shape drawn at the open ends of stroked lines and dashes
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineCap {
+    /// The stroke ends exactly at the end point
+    Butt,
+    /// The stroke ends with a half circle around the end point
+    Round,
+    /// The stroke ends with a half square around the end point
+    Square
+}
+
+/// The shape drawn where two stroked segments meet
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineJoin {
+    /// The outer edges are extended until they meet, unless that would exceed the miter limit
+    Miter,
+    /// The corner is rounded off with a circular arc
+    Round,
+    /// The corner is cut off with a straight line
+    Bevel
+}
+
+/// How the outlines drawn by stroking operations are shaped. The width of a stroke is still given
+/// to each operation, and dash lengths are multiples of it so a pattern looks the same at any width
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StrokeStyle {
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// The limit on the ratio of a miter's length to the stroke width, past which corners are
+    /// beveled instead
+    pub miter_limit: f32,
+    /// Alternating lengths of dashes and gaps, starting with a dash. An empty pattern draws a solid line
+    pub dashes: Vec<f32>,
+    /// How far into the dash pattern the stroke starts
+    pub dash_offset: f32
+}
+
+impl StrokeStyle {
+    /// A dashed stroke with butt caps and miter joins
+    pub fn dashed(dashes: &[f32]) -> StrokeStyle {
+        StrokeStyle { dashes: dashes.to_vec(), ..StrokeStyle::default() }
+    }
+
+    /// Returns true if the dash pattern would actually draw dashes. Patterns with negative lengths,
+    /// or that add up to nothing, draw a solid line
+    pub fn is_dashed(&self) -> bool {
+        !self.dashes.is_empty() && self.dashes.iter().all(|d| *d >= 0.0) && self.dashes.iter().sum::<f32>() > 0.0
+    }
+}
+
+impl Default for StrokeStyle {
+    /// A solid stroke with butt caps and miter joins, the same as if no style was set
+    fn default() -> StrokeStyle {
+        StrokeStyle { cap: LineCap::Butt, join: LineJoin::Miter, miter_limit: 10.0, dashes: Vec::new(), dash_offset: 0.0 }
+    }
+}
+
+/// A single segment of a Path
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathSegment {
+    /// Start a new figure at a point
+    MoveTo(Point),
+    /// A straight line to a point
+    LineTo(Point),
+    /// A quadratic Bézier curve with a control point, ending at a point
+    QuadTo(Point, Point),
+    /// A cubic Bézier curve with two control points, ending at a point
+    CubicTo(Point, Point, Point),
+    /// A circular arc around `center`, swept from `start_angle` to `end_angle` in radians. Angles
+    /// increase clockwise, and if `end_angle` is less than `start_angle` the arc is swept
+    /// counter-clockwise instead
+    Arc { center: Point, radius: f32, start_angle: f32, end_angle: f32 },
+    /// A straight line back to the start of the current figure, closing it
+    Close
+}
+
+/// A shape made of lines and curves, which can be built once and then filled or stroked by a
+/// RenderContext
+#[derive(Clone,Debug,Default,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Path {
+    segments: Vec<PathSegment>
+}
+
+impl Path {
+    /// Create an empty path
+    pub fn new() -> Path {
+        Path { segments: Vec::new() }
+    }
+
+    /// Create a path outlining a rectangle with rounded corners. Each radius is limited to half the
+    /// width and height of the rectangle
+    pub fn rounded_rect(rect: Rect, radii: CornerRadii) -> Path {
+        use std::f32::consts::PI;
+        let max = rect.w.abs().min(rect.h.abs()) / 2.0;
+        let (x0, y0, x1, y1) = (rect.x, rect.y, rect.x + rect.w, rect.y + rect.h);
+        let mut path = Path::new();
+        // each corner is an arc through its quarter of the circle, ending where the next edge starts
+        let corners = [
+            (radii.top_left, Point::xy(x0, y0), Point::xy(1.0, 1.0), PI),
+            (radii.top_right, Point::xy(x1, y0), Point::xy(-1.0, 1.0), PI * 1.5),
+            (radii.bottom_right, Point::xy(x1, y1), Point::xy(-1.0, -1.0), 0.0),
+            (radii.bottom_left, Point::xy(x0, y1), Point::xy(1.0, -1.0), PI * 0.5),
+        ];
+        for &(r, corner, inward, start_angle) in corners.iter() {
+            let r = r.max(0.0).min(max);
+            if r > 0.0 {
+                let center = Point::xy(corner.x + inward.x * r, corner.y + inward.y * r);
+                path.arc_to(center, r, start_angle, start_angle + PI * 0.5);
+            } else if path.is_empty() {
+                path.move_to(corner);
+            } else {
+                path.line_to(corner);
+            }
+        }
+        path.close();
+        path
+    }
+
+    /// Create a path outlining an ellipse around `center` with radii `rx` and `ry`
+    pub fn ellipse(center: Point, rx: f32, ry: f32) -> Path {
+        // the distance to the control points that best approximates a quarter circle with a cubic curve
+        const KAPPA: f32 = 0.552_284_8;
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+        let Point { x, y } = center;
+        let mut path = Path::new();
+        path.move_to(Point::xy(x + rx, y))
+            .cubic_to(Point::xy(x + rx, y + ky), Point::xy(x + kx, y + ry), Point::xy(x, y + ry))
+            .cubic_to(Point::xy(x - kx, y + ry), Point::xy(x - rx, y + ky), Point::xy(x - rx, y))
+            .cubic_to(Point::xy(x - rx, y - ky), Point::xy(x - kx, y - ry), Point::xy(x, y - ry))
+            .cubic_to(Point::xy(x + kx, y - ry), Point::xy(x + rx, y - ky), Point::xy(x + rx, y))
+            .close();
+        path
+    }
+
+    /// Start a new figure at `p`
+    pub fn move_to(&mut self, p: Point) -> &mut Path {
+        self.segments.push(PathSegment::MoveTo(p));
+        self
+    }
+
+    /// Add a straight line from the current point to `p`
+    pub fn line_to(&mut self, p: Point) -> &mut Path {
+        self.segments.push(PathSegment::LineTo(p));
+        self
+    }
+
+    /// Add a quadratic Bézier curve from the current point to `p`, with control point `c`
+    pub fn quad_to(&mut self, c: Point, p: Point) -> &mut Path {
+        self.segments.push(PathSegment::QuadTo(c, p));
+        self
+    }
+
+    /// Add a cubic Bézier curve from the current point to `p`, with control points `c1` and `c2`
+    pub fn cubic_to(&mut self, c1: Point, c2: Point, p: Point) -> &mut Path {
+        self.segments.push(PathSegment::CubicTo(c1, c2, p));
+        self
+    }
+
+    /// Add a circular arc around `center`, swept from `start_angle` to `end_angle` in radians. If
+    /// the path has a current point, a straight line is added from it to the start of the arc
+    pub fn arc_to(&mut self, center: Point, radius: f32, start_angle: f32, end_angle: f32) -> &mut Path {
+        self.segments.push(PathSegment::Arc { center, radius, start_angle, end_angle });
+        self
+    }
+
+    /// Close the current figure with a straight line back to its start
+    pub fn close(&mut self) -> &mut Path {
+        self.segments.push(PathSegment::Close);
+        self
+    }
+
+    /// The segments that make up this path, in order
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// Returns true if this path has no segments
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+/// A shape that can be given to operations which work on any kind of outline, such as shadows
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Shape {
+    Rect(Rect),
+    RoundedRect(Rect, CornerRadii),
+    Ellipse { center: Point, rx: f32, ry: f32 },
+    Path(Path)
+}
+
+impl Shape {
+    /// The outline of this shape as a path
+    pub fn to_path(&self) -> Path {
+        match self {
+            Shape::Rect(r) => {
+                let mut path = Path::new();
+                path.move_to(Point::xy(r.x, r.y))
+                    .line_to(Point::xy(r.x + r.w, r.y))
+                    .line_to(Point::xy(r.x + r.w, r.y + r.h))
+                    .line_to(Point::xy(r.x, r.y + r.h))
+                    .close();
+                path
+            },
+            Shape::RoundedRect(r, radii) => Path::rounded_rect(*r, *radii),
+            Shape::Ellipse { center, rx, ry } => Path::ellipse(*center, *rx, *ry),
+            Shape::Path(path) => path.clone()
+        }
+    }
+}
+
+impl From<Rect> for Shape {
+    fn from(r: Rect) -> Shape {
+        Shape::Rect(r)
+    }
+}
+
+impl From<Path> for Shape {
+    fn from(path: Path) -> Shape {
+        Shape::Path(path)
+    }
+}
+
+/// An owned buffer of 8-bit RGBA pixels with straight (not premultiplied) alpha
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PixelBuffer {
+    pub width: u32, pub height: u32,
+    /// The number of bytes between the start of each row in `data`
+    pub stride: usize,
+    pub data: Vec<u8>
+}
+
+impl PixelBuffer {
+    /// Create a buffer of transparent black pixels
+    pub fn new(width: u32, height: u32) -> PixelBuffer {
+        PixelBuffer {
+            width, height,
+            stride: width as usize * 4,
+            data: vec![0; width as usize * height as usize * 4]
+        }
+    }
+
+    /// Get the RGBA components of the pixel at (`x`, `y`)
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = y as usize * self.stride + x as usize * 4;
+        [self.data[i], self.data[i+1], self.data[i+2], self.data[i+3]]
+    }
+
+    /// Load a PNG image from a file
+    pub fn load_png<P: AsRef<std::path::Path>>(path: P) -> Result<PixelBuffer, Box<dyn Error>> {
+        imp::load_png(path.as_ref())
+    }
+
+    /// Write this buffer to a file as a PNG image
+    pub fn write_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        imp::write_png(self, path.as_ref())
+    }
+}
+
+/// Vector file formats that a RenderContext can render into
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DocumentFormat {
+    Svg, Pdf
+}
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FontWeight {
     Light, Regular, Bold
 }
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FontStyle {
     Normal, Italic
 }
 
+/// Where lines of a text layout can be broken to fit its width
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WrapMode {
+    /// Between words. Words too long for a line overflow it
+    Word,
+    /// Between any two characters
+    Char,
+    /// Between words, or between characters of words too long for a line
+    WordChar,
+    /// Only at line breaks in the text
+    None
+}
+
+/// Where text that doesn't fit in a layout is replaced with an ellipsis
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EllipsizeMode {
+    /// Text that doesn't fit overflows the layout
+    None,
+    Start,
+    Middle,
+    End
+}
+
+/// Measurements of a font at its size, in points
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontMetrics {
+    /// How far the font reaches above the baseline
+    pub ascent: f32,
+    /// How far the font reaches below the baseline, as a positive distance
+    pub descent: f32,
+    /// The height of lowercase letters like x above the baseline
+    pub x_height: f32
+}
+
+/// Where a line of a text layout is, and which text is on it
+#[derive(Clone,Debug,PartialEq)]
+pub struct LineMetrics {
+    /// The text on the line, in the same units as the range methods of `TextLayoutExt`. The line
+    /// break at the end of a paragraph isn't included
+    pub range: Range<usize>,
+    /// The area the line takes up, relative to the layout like `char_bounds`
+    pub bounds: Rect,
+    /// How far the baseline of the line is from the top of the layout
+    pub baseline: f32
+}
+
+/// How the lines of a paragraph are placed across the width of a text layout
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextAlignment {
+    Left, Center, Right,
+    /// Stretch every line but the last of each paragraph to fill the width
+    Justify
+}
+
+/// Formatting that applies to whole paragraphs of a text layout. Distances are in points
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParagraphStyle {
+    pub alignment: TextAlignment,
+    /// Multiplies the distance between the baselines of lines
+    pub line_spacing: f32,
+    /// How far the first line of each paragraph is indented. Negative values indent every line but
+    /// the first instead. DirectWrite can't indent lines, so this is ignored on Windows
+    pub first_line_indent: f32,
+    /// Extra space between the end of one paragraph and the start of the next
+    pub paragraph_spacing: f32,
+    /// The positions of tab stops from the start of each line. Tabs past the last stop are spaced
+    /// like the last two. DirectWrite only has evenly spaced stops, so on Windows they are all
+    /// spaced like the first
+    pub tab_stops: Vec<f32>
+}
+
+impl Default for ParagraphStyle {
+    fn default() -> ParagraphStyle {
+        ParagraphStyle {
+            alignment: TextAlignment::Left, line_spacing: 1.0, first_line_indent: 0.0, paragraph_spacing: 0.0, tab_stops: Vec::new()
+        }
+    }
+}
+
+/// Everything needed to create a Font again with `RenderContextExt::new_font`
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontDescription {
+    pub name: String, pub size: f32, pub weight: FontWeight, pub style: FontStyle
+}
+
+/// How the contents of a layer are combined with what is underneath it
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    /// The layer is drawn over what is underneath
+    Normal,
+    /// The colors are multiplied, which always darkens
+    Multiply,
+    /// The inverted colors are multiplied and inverted again, which always lightens
+    Screen,
+    /// Multiply or Screen, depending on the color underneath
+    Overlay,
+    /// The darker of the two colors is kept
+    Darken,
+    /// The lighter of the two colors is kept
+    Lighten,
+    /// The difference between the two colors
+    Difference
+}
+
+/// How an image is sampled when it is drawn at a different size than its pixels
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImageFilter {
+    /// Use the nearest pixel, keeping hard edges. Best for pixel art and integer scales
+    Nearest,
+    /// Blend between neighbouring pixels
+    Linear
+}
+
 pub type Font = imp::Font;
 pub type TextLayout = imp::TextLayout;
+pub type Image = imp::Image;
 pub type RenderContext = imp::RenderContext;
 
+pub trait ImageExt {
+    /// The size of this image in pixels, as a rectangle at the origin
+    fn bounds(&self) -> Rect;
+}
+
+pub trait FontExt {
+    /// Describe this font, as it was created
+    fn description(&self) -> FontDescription;
+
+    /// Measure the font. If it can't be loaded the metrics are all zero
+    fn metrics(&self) -> FontMetrics;
+}
+
 pub trait TextLayoutExt {
     /// Calculate the bounding rectangle of this text layout
     fn bounds(&self) -> Rect;
@@ -194,28 +795,66 @@ pub trait TextLayoutExt {
     /// to the layout's internal coordinate system, given by `bounds()`
     fn hit_test(&self, p: Point) -> Option<(usize, Rect)>;
 
+    /// Measure each line of the layout, from the top down
+    fn lines(&self) -> Vec<LineMetrics>;
+
     fn color_range(&self, rx: &RenderContext, range: Range<u32>, col: Color);
     fn style_range(&self, range: Range<u32>, style: FontStyle);
     fn weight_range(&self, range: Range<u32>, weight: FontWeight);
     fn underline_range(&self, range: Range<u32>, ul: bool);
     fn size_range(&self, range: Range<u32>, size: f32);
+    fn background_range(&self, range: Range<u32>, col: Color);
+    fn family_range(&self, range: Range<u32>, family: &str);
+
+    /// Choose where lines are broken to fit the layout's width. Layouts wrap at words by default
+    fn set_wrap(&self, wrap: WrapMode);
+
+    /// Choose where text is ellipsized when it doesn't fit the layout's width, or its height if it
+    /// wraps. Layouts don't ellipsize by default
+    fn set_ellipsize(&self, mode: EllipsizeMode);
+
+    /// Limit the layout to `lines` lines as well as its height, or only its height if None. Like
+    /// the height, this only takes effect when the layout ellipsizes
+    fn set_max_lines(&self, lines: Option<u32>);
+
+    /// Format every paragraph of the layout with `style`
+    fn set_paragraph_style(&self, style: &ParagraphStyle);
 }
 
 pub trait RenderContextExt {
     /// Create a new RenderContext, and resize the window to be in DIP units
     fn new(win: &mut winit::window::Window) -> Result<Self, Box<dyn Error>> where Self: Sized;
 
+    /// Create a new RenderContext that isn't attached to a window, rendering into an image `w` by
+    /// `h` pixels in size. This doesn't require a window system, so it can be used headless
+    fn new_offscreen(w: u32, h: u32) -> Result<Self, Box<dyn Error>> where Self: Sized;
+
+    /// Create a new RenderContext that renders vector output into a document file at `path`, with
+    /// pages `w` by `h` points in size. Each `start_paint`/`end_paint` pair produces one page,
+    /// although SVG files can only hold the first. The file is finished when the RenderContext is
+    /// dropped
+    fn new_document(path: &std::path::Path, w: u32, h: u32, format: DocumentFormat) -> Result<Self, Box<dyn Error>> where Self: Sized;
+
     /// Create a new font, looking the name up in the system font registery
     fn new_font(&self, name: &str, size: f32, weight: FontWeight, style: FontStyle) -> Result<Font, Box<dyn Error>>;
 
-    /// Create a new text layout. The text will be wrapped to `width` and `height`
+    /// Create a new text layout. The text will be wrapped to `width`, and ellipsized to fit `height`
+    /// if the layout is set to ellipsize
     fn new_text_layout(&self, text: &str, f: &Font, width: f32, height: f32) -> Result<TextLayout, Box<dyn Error>>;
 
+    /// Create a new text layout of `text` with its runs of styling applied, in place of `f` where
+    /// they cover it
+    fn new_attributed_text_layout(&self, text: &AttributedString, f: &Font, width: f32, height: f32) -> Result<TextLayout, Box<dyn Error>>;
+
     /// Clear the window
     fn clear(&mut self, col: Color);
 
     fn set_color(&mut self, col: Color);
 
+    /// Set the paint used by every fill, stroke and text drawing operation from now on. This
+    /// replaces the color set by `set_color`, and `set_color` replaces it in turn
+    fn set_brush(&mut self, brush: &Brush);
+
     /// Draw a rectangle, only the outline
     fn stroke_rect(&mut self, rect: Rect, stroke_width: f32);
 
@@ -225,6 +864,31 @@ pub trait RenderContextExt {
     /// Draw a line
     fn draw_line(&mut self, a: Point, b: Point, stroke_width: f32);
 
+    /// Set the caps, joins and dashes used by every stroking operation from now on
+    fn set_stroke_style(&mut self, style: &StrokeStyle);
+
+    /// Fill the inside of a path
+    fn fill_path(&mut self, path: &Path);
+
+    /// Draw the outline of a path
+    fn stroke_path(&mut self, path: &Path, stroke_width: f32);
+
+    /// Draw a filled rectangle with rounded corners
+    fn fill_rounded_rect(&mut self, rect: Rect, radii: CornerRadii);
+
+    /// Draw a rectangle with rounded corners, only the outline
+    fn stroke_rounded_rect(&mut self, rect: Rect, radii: CornerRadii, stroke_width: f32);
+
+    /// Draw a filled ellipse around `center` with radii `rx` and `ry`
+    fn fill_ellipse(&mut self, center: Point, rx: f32, ry: f32);
+
+    /// Draw an ellipse around `center` with radii `rx` and `ry`, only the outline
+    fn stroke_ellipse(&mut self, center: Point, rx: f32, ry: f32, stroke_width: f32);
+
+    /// Draw the soft shadow that `shape` would cast, moved by `offset` and blurred by
+    /// `blur_radius`, which is roughly how far the shadow fades out past the edge of the shape
+    fn draw_shadow(&mut self, shape: &Shape, offset: Point, blur_radius: f32, color: Color);
+
     /// Draw text, wrapped within `rect`
     ///
     /// This function is best for dynamic text, that won't need to be measured
@@ -236,34 +900,237 @@ pub trait RenderContextExt {
     /// frequently
     fn draw_text_layout(&mut self, p: Point, txl: &TextLayout);
 
+    /// Create an image from a buffer of pixels, ready to be drawn
+    fn new_image(&self, pixels: &PixelBuffer) -> Result<Image, Box<dyn Error>>;
+
+    /// Load an image from a PNG file
+    fn load_image(&self, path: &std::path::Path) -> Result<Image, Box<dyn Error>> {
+        self.new_image(&PixelBuffer::load_png(path)?)
+    }
+
+    /// Draw the part of `img` inside `src`, in pixels of the image, scaled to fill `dst`
+    fn draw_image(&mut self, img: &Image, src: Rect, dst: Rect, opacity: f32, filter: ImageFilter);
+
+    /// Create an offscreen RenderContext `w` by `h` points in size, at the same scale factor as this
+    /// one, that can be drawn into this one efficiently. Content that rarely changes can be painted into it once, with `start_paint` and
+    /// `end_paint` as usual, and drawn each frame with `draw_render_target`
+    fn new_render_target(&self, w: u32, h: u32) -> Result<Self, Box<dyn Error>> where Self: Sized;
+
+    /// Draw what was last painted into `target`, with its top left corner at the origin, transformed
+    /// by `m` on top of the current transform. Like images, it's filtered smoothly when scaled but
+    /// its edges are kept sharp
+    fn draw_render_target(&mut self, target: &Self, m: &Affine, opacity: f32) where Self: Sized;
+
     /// Translate the origin point that primitives will be drawn relative to
     ///
     /// Default value is (0,0)
     fn translate(&mut self, p: Point);
 
-    /// Calculate the size of the area being rendered into
+    /// Apply `m` to everything drawn from now on, before the current transform
+    fn transform(&mut self, m: &Affine);
+
+    /// Replace the current transform with `m`
+    fn set_transform(&mut self, m: &Affine);
+
+    /// The transform that maps the coordinates primitives are drawn in to points, leaving out the
+    /// scale factor. Its inverse maps points from events back into drawing coordinates
+    fn current_transform(&self) -> Affine;
+
+    /// Save the current transform, so it can be returned to with `restore`
+    fn save(&mut self);
+
+    /// Return to the transform saved by the matching `save`. The transform is reset to the
+    /// identity by `start_paint`, which also forgets any saved transforms
+    fn restore(&mut self);
+
+    /// Restrict drawing to the inside of `rect`, intersected with any clips already pushed, until
+    /// the matching `pop_clip`
+    fn push_clip_rect(&mut self, rect: Rect);
+
+    /// Restrict drawing to the inside of `path`, intersected with any clips already pushed, until
+    /// the matching `pop_clip`
+    fn push_clip_path(&mut self, path: &Path);
+
+    /// Remove the most recently pushed clip. Any clips still pushed are removed by `start_paint`.
+    /// Clips and layers must be popped in the reverse order they were pushed
+    fn pop_clip(&mut self);
+
+    /// Start drawing into a new transparent layer. When the layer is popped, it is composited onto
+    /// what was underneath it with `opacity` and `blend`
+    fn push_layer(&mut self, opacity: f32, blend: BlendMode);
+
+    /// Composite the most recently pushed layer. Any clips pushed since the layer are also popped,
+    /// and any layers still pushed are composited by `start_paint`
+    fn pop_layer(&mut self);
+
+    /// Calculate the size of the area being rendered into, in points
     fn bounds(&self) -> Rect;
 
+    /// Copy the current contents of the area being rendered into out as RGBA pixels
+    fn read_pixels(&self) -> Result<PixelBuffer, Box<dyn Error>>;
+
+    /// Write the current contents of the area being rendered into to a PNG file
+    fn write_png(&self, path: &std::path::Path) -> Result<(), Box<dyn Error>>;
+
     /// Start the painting process. Must be called before any drawing functions
     fn start_paint(&mut self);
+    /// Start painting only the damaged parts of the area being rendered into. Drawing is clipped
+    /// to the bounds of `damage` and anything outside it is left as it was. If everything is
+    /// damaged, or the contents aren't kept between frames, this paints everything like
+    /// `start_paint`
+    fn start_paint_damaged(&mut self, damage: &Damage);
+    /// The area being painted in this frame, which is the whole of `bounds` unless painting was
+    /// started with `start_paint_damaged`. Drawing outside it can be skipped
+    fn damaged_rect(&self) -> Rect;
     /// End the painting process. Call after finishing drawing
     fn end_paint(&mut self);
 
-    /// Resize this RenderContext
-    fn resize(&mut self, w: u32, h: u32);
+    /// Resize this RenderContext, or return an error and keep the old size if it can't be
+    fn resize(&mut self, w: u32, h: u32) -> Result<(), Box<dyn Error>>;
 
     /// Convert a point that is in screen pixels to a point that is Device Independent Points.
     /// There are 96 DIPs in an inch
     fn pixels_to_points(&self, p: Point) -> Point;
+
+    /// The number of pixels per point. Everything is drawn in points, so on high DPI displays it
+    /// is scaled up by this much
+    fn scale_factor(&self) -> f32;
+
+    /// Change the number of pixels per point. The size in pixels stays the same, so `bounds`
+    /// shrinks as the scale factor grows. Windows are kept at the scale factor of their display,
+    /// but offscreen RenderContexts start at 1 and can be set to anything
+    fn set_scale_factor(&mut self, scale: f32);
 }
 
 pub use winit::event::{MouseButton, VirtualKeyCode, ElementState, TouchPhase, ModifiersState, KeyboardInput};
 pub use winit::dpi as dpi;
 pub use winit::event::WindowEvent as Event;
 pub use winit::event_loop::ControlFlow as ControlFlowOpts;
+pub use winit::event_loop::EventLoopProxy as EventProxy;
 pub use winit::window::Window as Window;
 pub use winit::window::WindowBuilder as WindowOptions;
 
+/// The parts of a window that need to be redrawn, as rectangles in the same coordinates used
+/// for drawing
+#[derive(Clone,Debug,Default,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Damage {
+    rects: Vec<Rect>,
+    all: bool
+}
+
+impl Damage {
+    /// Create an empty Damage, where nothing needs to be redrawn
+    pub fn new() -> Damage {
+        Damage::default()
+    }
+
+    /// Mark `rect` as needing to be redrawn. Empty rectangles are ignored
+    pub fn add(&mut self, rect: Rect) {
+        if !self.all && !rect.is_empty() {
+            self.rects.push(rect);
+        }
+    }
+
+    /// Mark everything as needing to be redrawn
+    pub fn add_all(&mut self) {
+        self.all = true;
+        self.rects.clear();
+    }
+
+    /// Returns true if nothing needs to be redrawn
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.rects.is_empty()
+    }
+
+    /// Returns true if everything needs to be redrawn
+    pub fn is_all(&self) -> bool {
+        self.all
+    }
+
+    /// The rectangles that have been marked as needing to be redrawn
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    /// The smallest rectangle containing everything that needs to be redrawn, or None if nothing
+    /// or everything does
+    pub fn bounds(&self) -> Option<Rect> {
+        if self.all {
+            return None;
+        }
+        let mut rects = self.rects.iter();
+        let first = *rects.next()?;
+        Some(rects.fold(first, |u, r| u.union(*r)))
+    }
+
+    /// Mark everything in `other` as needing to be redrawn too
+    pub fn merge(&mut self, other: &Damage) {
+        if other.all {
+            self.add_all();
+        } else {
+            for r in &other.rects {
+                self.add(*r);
+            }
+        }
+    }
+
+    /// Forget all the damage, once it has been redrawn
+    pub fn clear(&mut self) {
+        self.rects.clear();
+        self.all = false;
+    }
+}
+
+/// Identifies one of an App's windows. The window the App is started with is `WindowId::MAIN`
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub struct WindowId(u64);
+
+impl WindowId {
+    /// The window the App is started with
+    pub const MAIN: WindowId = WindowId(0);
+}
+
+/// The open windows of an App, which can be used to open more windows or close them while the App
+/// handles an event
+pub struct Windows<'a> {
+    open_window: &'a mut dyn FnMut(WindowId, WindowOptions) -> Result<(), Box<dyn Error>>,
+    next_id: &'a mut u64,
+    ids: Vec<WindowId>,
+    closed: Vec<WindowId>
+}
+
+impl<'a> Windows<'a> {
+    fn new(open_window: &'a mut dyn FnMut(WindowId, WindowOptions) -> Result<(), Box<dyn Error>>,
+           next_id: &'a mut u64, ids: Vec<WindowId>) -> Windows<'a> {
+        Windows { open_window, next_id, ids, closed: Vec::new() }
+    }
+
+    /// Open a new window with its own RenderContext. Its events are passed to `App::window_event`
+    /// and it is painted by `App::paint_window`, along with the id that is returned
+    pub fn open(&mut self, opts: WindowOptions) -> Result<WindowId, Box<dyn Error>> {
+        let id = WindowId(*self.next_id);
+        (self.open_window)(id, opts)?;
+        *self.next_id += 1;
+        self.ids.push(id);
+        Ok(id)
+    }
+
+    /// Close a window once the current event has been handled. Closing windows, even the last one,
+    /// never exits the event loop by itself
+    pub fn close(&mut self, id: WindowId) {
+        if let Some(i) = self.ids.iter().position(|w| *w == id) {
+            self.ids.remove(i);
+            self.closed.push(id);
+        }
+    }
+
+    /// The ids of the windows that are open
+    pub fn ids(&self) -> &[WindowId] {
+        &self.ids
+    }
+}
+
 /// The App trait represents an application that uses RenderContext to draw its interface.
 /// The `run` function is provided to conveniently set up the loop that handles winit events and
 /// redraws the App interface using `paint`
@@ -284,54 +1151,289 @@ pub trait App {
     /// set to `true` if this event invalidates the current window contents, otherwise it can be
     /// ignored.
     fn event(&mut self, e: Event, event_loop_flow: &mut ControlFlowOpts, should_redraw: &mut bool);
+
+    /// Handle an event like `event`, but report which parts of the window it invalidates by adding
+    /// them to `damage` so that only those parts are repainted. Damage from several events is
+    /// accumulated until the next frame. By default this calls `event` and damages the whole
+    /// window if it sets `should_redraw`
+    fn event_with_damage(&mut self, e: Event, event_loop_flow: &mut ControlFlowOpts, damage: &mut Damage) {
+        let mut should_redraw = false;
+        self.event(e, event_loop_flow, &mut should_redraw);
+        if should_redraw {
+            damage.add_all();
+        }
+    }
+
+    /// Handle an event for one of the App's windows, which can use `windows` to open or close
+    /// windows. Windows aren't closed unless the App closes them, so the App should close any
+    /// window other than the main one when it gets `Event::CloseRequested`. By default this calls
+    /// `event_with_damage`, whichever window the event is for
+    fn window_event(&mut self, _window: WindowId, e: Event, event_loop_flow: &mut ControlFlowOpts,
+                    damage: &mut Damage, _windows: &mut Windows) {
+        self.event_with_damage(e, event_loop_flow, damage);
+    }
+
+    /// Draw the interface for one of the App's windows. By default this calls `paint`, whichever
+    /// window is being painted
+    fn paint_window(&mut self, _window: WindowId, rx: &mut RenderContext) {
+        self.paint(rx);
+    }
+
+    /// The time at which the App next wants `timer` to be called, if any. This is checked after
+    /// every event and timer, so it can be changed at any time
+    fn next_timer(&self) -> Option<Instant> { None }
+
+    /// Called once the time returned by `next_timer` has passed, with the current time. Parts of
+    /// the main window that need to be redrawn should be added to `damage`
+    fn timer(&mut self, _now: Instant, _damage: &mut Damage) {}
+
+    /// Returns true while the App is animating. Frames are painted in every window continuously,
+    /// about 60 times a second, and `tick` is called before each one
+    fn is_animating(&self) -> bool { false }
+
+    /// Advance animations by `dt`, the time since the last tick or since the App started animating
+    fn tick(&mut self, _dt: Duration) {}
+}
+
+/// An App that can receive events of its own type, sent from other threads through an
+/// `EventProxy`. Run it with `start_with_user_events`
+pub trait UserEventApp: App {
+    /// The type of event sent to this App
+    type UserEvent: 'static + Send;
+
+    /// Called once after `init` with a proxy that can be cloned and sent to other threads. Events
+    /// sent through it wake up the event loop and are delivered to `user_event`
+    fn event_proxy(&mut self, proxy: EventProxy<Self::UserEvent>);
+
+    /// Handle an event sent through an `EventProxy`. `event_loop_flow` and `should_redraw` work
+    /// the same as they do for `App::event`, with `should_redraw` redrawing the main window
+    fn user_event(&mut self, e: Self::UserEvent, event_loop_flow: &mut ControlFlowOpts, should_redraw: &mut bool);
+}
+
+/// How often frames are painted while an App is animating
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// Call `AppT::timer` for as long as its next timer is due at `now`
+fn fire_timers<AppT: App>(app: &mut AppT, now: Instant, damage: &mut Damage) {
+    while let Some(t) = app.next_timer().filter(|t| *t <= now) {
+        app.timer(now, damage);
+        // a timer that firing doesn't move on would fire forever
+        if app.next_timer() == Some(t) {
+            break;
+        }
+    }
+}
+
+/// Decides when timers fire and animation frames are painted. The event loop and a Driver's
+/// simulated clock both update one whenever they wake up, so Apps run the same way under both
+#[derive(Default)]
+struct FrameClock {
+    // when the last animation frame was ticked, while the App is animating
+    last_tick: Option<Instant>
+}
+
+impl FrameClock {
+    /// Fire the timers that are due at `now`, adding what they damage in the main window to
+    /// `damage`, then tick the App if an animation frame is due. Returns true if a frame should be
+    /// painted in every window
+    fn update<AppT: App>(&mut self, app: &mut AppT, now: Instant, damage: &mut Damage) -> bool {
+        fire_timers(app, now, damage);
+        if !app.is_animating() {
+            self.last_tick = None;
+            return false;
+        }
+        match self.last_tick {
+            // start animating with a frame straight away
+            None => {
+                self.last_tick = Some(now);
+                true
+            },
+            Some(t) if now >= t + FRAME_INTERVAL => {
+                app.tick(now - t);
+                self.last_tick = Some(now);
+                true
+            },
+            _ => false
+        }
+    }
+
+    /// The next time `update` needs to be called, for a timer or an animation frame
+    fn next_wake<AppT: App>(&self, app: &AppT) -> Option<Instant> {
+        app.next_timer().into_iter().chain(self.last_tick.map(|t| t + FRAME_INTERVAL)).min()
+    }
+}
+
+/// Combine the control flow an App asked for with the next time it needs to wake up for a timer or
+/// an animation frame
+fn schedule(flow: ControlFlowOpts, wake: Option<Instant>) -> ControlFlowOpts {
+    match (flow, wake) {
+        (ControlFlowOpts::Wait, Some(t)) => ControlFlowOpts::WaitUntil(t),
+        (ControlFlowOpts::WaitUntil(u), Some(t)) => ControlFlowOpts::WaitUntil(u.min(t)),
+        (flow, _) => flow
+    }
+}
+
+/// Apply the runs of `text` to `txl`, a layout of the same text. `index` converts byte offsets into
+/// the positions the backend's range methods take, and `color` sets the color of a range, which
+/// `color_range` needs a RenderContext for
+fn apply_text_runs<I, C>(txl: &TextLayout, text: &AttributedString, index: I, color: C)
+    where I: Fn(usize) -> u32, C: Fn(Range<u32>, Color) {
+    for run in text.runs() {
+        let range = index(run.range.start)..index(run.range.end);
+        match &run.attribute {
+            TextAttribute::Color(col) => color(range, *col),
+            TextAttribute::Background(col) => txl.background_range(range, *col),
+            TextAttribute::Weight(weight) => txl.weight_range(range, *weight),
+            TextAttribute::Style(style) => txl.style_range(range, *style),
+            TextAttribute::Underline(ul) => txl.underline_range(range, *ul),
+            TextAttribute::Size(size) => txl.size_range(range, *size),
+            TextAttribute::Family(family) => txl.family_range(range, family)
+        }
+    }
+}
+
+/// Deliver a window event to `app` the same way `start` does, scaling cursor positions into points
+/// and resizing `rx` as necessary. Returns true if the whole window needs to be redrawn regardless
+/// of `damage`
+fn deliver_event<AppT: App>(app: &mut AppT, window: WindowId, rx: &mut RenderContext, event: Event,
+                            ctrl_flow: &mut ControlFlowOpts, damage: &mut Damage, windows: &mut Windows) -> bool {
+    #[allow(deprecated)]
+    match event {
+        winit::event::WindowEvent::CursorMoved { device_id, position, modifiers } =>  {
+            let scaled = rx.pixels_to_points(Point { x: position.x as f32, y: position.y as f32 });
+            app.window_event(window, winit::event::WindowEvent::CursorMoved {
+                device_id, position: dpi::PhysicalPosition{ x: scaled.x as f64, y: scaled.y as f64 }, modifiers 
+            }, ctrl_flow, damage, windows);
+            false
+        },
+        winit::event::WindowEvent::Resized(size) => {
+            rx.resize(size.width, size.height).expect("resize render context");
+            app.window_event(window, event, ctrl_flow, damage, windows);
+            true
+        },
+        winit::event::WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+            rx.set_scale_factor(scale_factor as f32);
+            app.window_event(window, winit::event::WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size },
+                             ctrl_flow, damage, windows);
+            // the App can change the size the window will have at the new scale
+            rx.resize(new_inner_size.width, new_inner_size.height).expect("resize render context");
+            true
+        },
+        _=> {
+            app.window_event(window, event, ctrl_flow, damage, windows);
+            false
+        }
+    }
+}
+
+/// Deliver an event sent through an `EventProxy` to `app`, the same way `start_with_user_events`
+/// does
+fn deliver_user_event<AppT: UserEventApp>(app: &mut AppT, e: AppT::UserEvent, ctrl_flow: &mut ControlFlowOpts, damage: &mut Damage) {
+    let mut should_redraw = false;
+    app.user_event(e, ctrl_flow, &mut should_redraw);
+    if should_redraw {
+        damage.add_all();
+    }
 }
 
 /// Start an runic app specified by `AppT` and run the event loop
 /// the WindowOptions will be used to create the window the app will run in
 pub fn start<AppT: 'static + App>(winopts: WindowOptions) -> ! {
+    run::<AppT, ()>(winopts, |_, _| (), |_, _, _, _| ())
+}
+
+/// Start an runic app like `start`, but with an event loop that also delivers events sent from
+/// other threads to `UserEventApp::user_event`
+pub fn start_with_user_events<AppT: 'static + UserEventApp>(winopts: WindowOptions) -> ! {
+    run::<AppT, AppT::UserEvent>(winopts, |app, el| app.event_proxy(el.create_proxy()), deliver_user_event)
+}
+
+/// A window opened by an App, with the damage that hasn't been painted yet
+struct OpenWindow {
+    id: WindowId,
+    window: Window,
+    rx: RenderContext,
+    damage: Damage
+}
+
+/// Run the event loop for `AppT`. `setup` is called once the App has been initialized and
+/// `user_event` delivers each event sent through the loop's proxies
+fn run<AppT, T>(winopts: WindowOptions,
+                setup: impl FnOnce(&mut AppT, &winit::event_loop::EventLoop<T>),
+                mut user_event: impl 'static + FnMut(&mut AppT, T, &mut ControlFlowOpts, &mut Damage)) -> !
+    where AppT: 'static + App, T: 'static
+{
     imp::init();
-    let el = winit::event_loop::EventLoop::new();
+    let el = winit::event_loop::EventLoop::with_user_event();
     let mut window = winopts.build(&el).expect("create new window"); 
     let mut rx = RenderContext::new(&mut window).expect("create render context");
     let mut app = AppT::init(&mut rx);
-    let mut should_redraw = false;
+    setup(&mut app, &el);
     app.configure_window(&mut window);
-    el.run(move |ev, _, ctrl_flow| {
+    let mut windows = vec![OpenWindow { id: WindowId::MAIN, window, rx, damage: Damage::new() }];
+    let mut next_id = 1;
+    let mut flow = ControlFlowOpts::Wait;
+    let mut clock = FrameClock::default();
+    el.run(move |ev, target, ctrl_flow| {
         use winit::event::Event;
-        use winit::event_loop::ControlFlow;
-        *ctrl_flow = ControlFlow::Wait;
         match ev {
-            Event::WindowEvent { event, .. } => {
-                #[allow(deprecated)]
-                match event {
-                    winit::event::WindowEvent::CursorMoved { device_id, position, modifiers } =>  {
-                        let scaled = rx.pixels_to_points(Point { x: position.x as f32, y: position.y as f32 });
-                        app.event(winit::event::WindowEvent::CursorMoved {
-                            device_id, position: dpi::PhysicalPosition{ x: scaled.x as f64, y: scaled.y as f64 }, modifiers 
-                        }, ctrl_flow, &mut should_redraw);
-                    },
-                    winit::event::WindowEvent::Resized(size) => {
-                        rx.resize(size.width, size.height);
-                        window.request_redraw();
-                        app.event(event, ctrl_flow, &mut should_redraw);
-                    },
-                    _=> {
-                        app.event(event, ctrl_flow, &mut should_redraw);
+            Event::NewEvents(_) => flow = ControlFlowOpts::Wait,
+            Event::WindowEvent { window_id, event } => {
+                let ids = windows.iter().map(|w| w.id).collect();
+                if let Some(w) = windows.iter_mut().find(|w| w.window.id() == window_id) {
+                    let mut opened = Vec::new();
+                    let mut open_window = |id, opts: WindowOptions| -> Result<(), Box<dyn Error>> {
+                        let mut window = opts.build(target)?;
+                        let rx = RenderContext::new(&mut window)?;
+                        opened.push(OpenWindow { id, window, rx, damage: Damage::new() });
+                        Ok(())
+                    };
+                    let mut wins = Windows::new(&mut open_window, &mut next_id, ids);
+                    if deliver_event(&mut app, w.id, &mut w.rx, event, &mut flow, &mut w.damage, &mut wins) {
+                        w.damage.add_all();
+                        w.window.request_redraw();
                     }
+                    let closed = wins.closed;
+                    windows.retain(|w| !closed.contains(&w.id));
+                    windows.extend(opened);
+                }
+            },
+            Event::UserEvent(e) => {
+                let mut damage = Damage::new();
+                user_event(&mut app, e, &mut flow, &mut damage);
+                if let Some(w) = windows.iter_mut().find(|w| w.id == WindowId::MAIN) {
+                    w.damage.merge(&damage);
                 }
             },
             Event::MainEventsCleared => {
-                if should_redraw {
-                    window.request_redraw();
-                    should_redraw = false;
+                let mut damage = Damage::new();
+                let frame = clock.update(&mut app, Instant::now(), &mut damage);
+                for w in windows.iter_mut() {
+                    // animation frames are painted in every window
+                    if frame {
+                        w.damage.add_all();
+                    } else if w.id == WindowId::MAIN {
+                        w.damage.merge(&damage);
+                    }
+                    if !w.damage.is_empty() {
+                        w.window.request_redraw();
+                    }
                 }
             },
-            Event::RedrawRequested(_) => {
-                rx.start_paint();
-                app.paint(&mut rx);
-                rx.end_paint();
+            Event::RedrawRequested(window_id) => {
+                if let Some(w) = windows.iter_mut().find(|w| w.window.id() == window_id) {
+                    // redraws the system asked for come without any damage, and need everything painted
+                    if w.damage.is_empty() {
+                        w.damage.add_all();
+                    }
+                    w.rx.start_paint_damaged(&w.damage);
+                    app.paint_window(w.id, &mut w.rx);
+                    w.rx.end_paint();
+                    w.damage.clear();
+                }
             }
             _ => ()
         }
+        *ctrl_flow = schedule(flow, clock.next_wake(&app));
     })
 }
@@ -1,4 +1,5 @@
-use super::{Rect, Point, Color, FontWeight, FontStyle};
+use super::{Rect, Point, Color, FontWeight, FontStyle, DocumentFormat};
+use std::path::Path;
 use std::error::Error;
 use std::os::raw::c_void;
 use std::mem::transmute;
@@ -30,8 +31,12 @@ use winit::os::macos::WindowExt;
 
 use cairo_context;
 
+pub use cairo_context::{load_png, write_png};
+pub(crate) use cairo_context::{layout_id, image_id};
+
 pub type Font = cairo_context::Font;
 pub type TextLayout = cairo_context::TextLayout;
+pub type Image = cairo_context::Image;
 pub type RenderContext = cairo_context::CairoRenderContext<QuartzCairoSurface>;
 
 pub fn init() { }
@@ -39,7 +44,9 @@ pub fn init() { }
 pub struct QuartzCairoSurface {
     qgx: *mut Object,
     surf: *mut cairo_surface_t,
-    size: (u32, u32), dpi_factor: f32
+    size: (u32, u32), dpi_factor: f32,
+    /// The number of pixels per point. Quartz scales drawing itself, so this is only reported
+    scale: f32
 }
 
 extern "C" {
@@ -65,13 +72,32 @@ impl cairo_context::CairoSurface for QuartzCairoSurface {
             cairo_surface_set_device_offset(surf, offset.0, offset.1 + height as f64);
             cairo_surface_set_device_scale(surf, scale.0, -scale.1);
             Ok(QuartzCairoSurface{
-                qgx: nsgx, surf, size: (width, height), dpi_factor: 1.0 / win.hidpi_factor()
+                qgx: nsgx, surf, size: (width, height), dpi_factor: 1.0 / win.hidpi_factor(),
+                scale: win.hidpi_factor() as f32
             })
         }
     }
 
+    fn new_offscreen(_w: u32, _h: u32) -> Result<Self, Box<Error>> where Self: Sized {
+        Err("offscreen rendering is not supported by the Quartz backend yet".into())
+    }
+
+    fn new_document(_path: &Path, _w: u32, _h: u32, _format: DocumentFormat) -> Result<Self, Box<Error>> where Self: Sized {
+        Err("document rendering is not supported by the Quartz backend yet".into())
+    }
+
+    fn new_similar(&self, _w: u32, _h: u32) -> Result<Self, Box<Error>> where Self: Sized {
+        Err("render targets are not supported by the Quartz backend yet".into())
+    }
+
     fn surface(&self) -> *mut cairo_surface_t { self.surf }
 
+    fn scale(&self) -> f32 { self.scale }
+
+    fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
     fn bounds(&self) -> Rect { Rect::xywh(0.0,0.0,self.size.0 as f32,self.size.1 as f32) }
 
     fn start_paint(&mut self) {
@@ -82,7 +108,7 @@ impl cairo_context::CairoSurface for QuartzCairoSurface {
         }
     }
 
-    fn resize(&mut self, width: u32, height: u32) {
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Box<Error>> {
         self.size = ((width as f32*self.dpi_factor) as u32, (height as f32*self.dpi_factor) as u32);
         unsafe {
             let cg: *mut c_void = msg_send![self.qgx, graphicsPort];
@@ -96,6 +122,7 @@ impl cairo_context::CairoSurface for QuartzCairoSurface {
             cairo_surface_set_device_offset(self.surf, offset.0, offset.1 + height as f64 * self.dpi_factor as f64);
             cairo_surface_set_device_scale(self.surf, scale.0, -scale.1);
         }
+        Ok(())
     }
 
     fn pixels_to_points(&self, p: Point) -> Point {
@@ -0,0 +1,132 @@
+//! Golden-image snapshot testing
+//!
+//! An App is rendered offscreen at a fixed size and the result is compared against a reference
+//! PNG image. When the `RUNIC_UPDATE_SNAPSHOTS` environment variable is set the reference is
+//! written instead, so new snapshots are created by running the tests once with it set and
+//! checking in the images. Without it a missing reference fails the test.
+
+use crate::*;
+use std::path::{Path, PathBuf};
+
+/// The result of comparing two images
+pub struct Comparison {
+    /// The number of pixels that differ by more than the tolerance
+    pub differing_pixels: usize,
+    /// The largest difference in any channel of any pixel
+    pub max_difference: u8,
+    /// An image with differing pixels highlighted in red over a faded copy of the expected image
+    pub diff_image: PixelBuffer
+}
+
+impl Comparison {
+    /// Returns true if no pixels differed by more than the tolerance
+    pub fn matches(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+/// Compare two images of the same size. Pixels are considered different if any of their
+/// channels differs by more than `tolerance`
+pub fn compare(actual: &PixelBuffer, expected: &PixelBuffer, tolerance: u8) -> Comparison {
+    assert_eq!((actual.width, actual.height), (expected.width, expected.height),
+        "compared images must be the same size");
+    let mut diff_image = PixelBuffer::new(actual.width, actual.height);
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let a = actual.pixel(x, y);
+            let e = expected.pixel(x, y);
+            let d = pixel_difference(a, e);
+            max_difference = max_difference.max(d);
+            let i = y as usize * diff_image.stride + x as usize * 4;
+            if d > tolerance {
+                differing_pixels += 1;
+                diff_image.data[i..i+4].copy_from_slice(&[255, 0, 0, 255]);
+            } else {
+                let luma = (e[0] as u32 * 3 + e[1] as u32 * 6 + e[2] as u32) / 10;
+                let faded = (luma * e[3] as u32 / 255 / 4 + 191) as u8;
+                diff_image.data[i..i+4].copy_from_slice(&[faded, faded, faded, 255]);
+            }
+        }
+    }
+    Comparison { differing_pixels, max_difference, diff_image }
+}
+
+/// The largest difference in any channel of two pixels
+fn pixel_difference(a: [u8; 4], b: [u8; 4]) -> u8 {
+    a.iter().zip(b.iter()).map(|(a, b)| (*a as i32 - *b as i32).unsigned_abs() as u8).max().unwrap_or(0)
+}
+
+/// Returns true if none of the channels of two pixels differ by more than `tolerance`
+pub fn near(a: [u8; 4], b: [u8; 4], tolerance: u8) -> bool {
+    pixel_difference(a, b) <= tolerance
+}
+
+/// Assert that the pixel of `actual` at `x`, `y` is within `tolerance` of `expected`
+pub fn assert_pixel(actual: &PixelBuffer, x: u32, y: u32, expected: [u8; 4], tolerance: u8) {
+    let found = actual.pixel(x, y);
+    assert!(near(found, expected, tolerance), "pixel at ({}, {}) is {:?}, expected {:?} within {}",
+        x, y, found, expected, tolerance);
+}
+
+/// Render one frame of `AppT` into an offscreen RenderContext `w` by `h` pixels in size
+pub fn render_app<AppT: App>(w: u32, h: u32) -> Result<PixelBuffer, Box<dyn Error>> {
+    let mut rx = RenderContext::new_offscreen(w, h)?;
+    let mut app = AppT::init(&mut rx);
+    rx.start_paint();
+    app.paint(&mut rx);
+    rx.end_paint();
+    rx.read_pixels()
+}
+
+fn sibling_path(reference: &Path, suffix: &str) -> PathBuf {
+    let stem = reference.file_stem().map_or("snapshot".into(), |s| s.to_string_lossy());
+    reference.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+/// Assert that `actual` matches the reference image at `reference` within `tolerance`.
+///
+/// On failure the actual image and a diff image are written next to the reference, with
+/// `.actual.png` and `.diff.png` extensions, and this function panics. A missing reference is a
+/// failure too, unless `RUNIC_UPDATE_SNAPSHOTS` is set.
+pub fn assert_snapshot<P: AsRef<Path>>(actual: &PixelBuffer, reference: P, tolerance: u8) {
+    let reference = reference.as_ref();
+    if std::env::var_os("RUNIC_UPDATE_SNAPSHOTS").is_some() {
+        if let Some(dir) = reference.parent() {
+            std::fs::create_dir_all(dir).expect("create snapshot directory");
+        }
+        actual.write_png(reference).expect("write reference snapshot");
+        return;
+    }
+    let actual_path = sibling_path(reference, "actual");
+    if !reference.exists() {
+        if let Some(dir) = reference.parent() {
+            std::fs::create_dir_all(dir).expect("create snapshot directory");
+        }
+        actual.write_png(&actual_path).expect("write actual snapshot");
+        panic!("snapshot {} reference missing, run with RUNIC_UPDATE_SNAPSHOTS=1 to create it",
+            reference.display());
+    }
+    let expected = PixelBuffer::load_png(reference).expect("load reference snapshot");
+    if (actual.width, actual.height) != (expected.width, expected.height) {
+        actual.write_png(&actual_path).expect("write actual snapshot");
+        panic!("snapshot {} is {}x{} but the rendered image is {}x{}",
+            reference.display(), expected.width, expected.height, actual.width, actual.height);
+    }
+    let cmp = compare(actual, &expected, tolerance);
+    if !cmp.matches() {
+        let diff_path = sibling_path(reference, "diff");
+        actual.write_png(&actual_path).expect("write actual snapshot");
+        cmp.diff_image.write_png(&diff_path).expect("write snapshot diff");
+        panic!("snapshot {} differs in {} pixels (max difference {}, tolerance {}), see {}",
+            reference.display(), cmp.differing_pixels, cmp.max_difference, tolerance, diff_path.display());
+    }
+}
+
+/// Render `AppT` offscreen at `w` by `h` pixels and assert that it matches the reference image at
+/// `reference` within `tolerance`. See `assert_snapshot`
+pub fn assert_app_snapshot<AppT: App, P: AsRef<Path>>(reference: P, w: u32, h: u32, tolerance: u8) {
+    let actual = render_app::<AppT>(w, h).expect("render app offscreen");
+    assert_snapshot(&actual, reference, tolerance);
+}
@@ -2,7 +2,7 @@
 use std::error::Error;
 use cairo_sys::*;
 use winit;
-use super::{Point, Rect};
+use super::{Point, Rect, DocumentFormat};
 
 #[cfg(feature = "x11")]
 use x11_dl::xlib::*;
@@ -16,8 +16,16 @@ use egl::egl;
 use winit::platform::unix::WindowExtUnix;
 use std::mem::{transmute, MaybeUninit};
 use std::ptr::{null, null_mut};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 
 use crate::cairo_context;
+use crate::cairo_context::{cairo_surface_set_device_scale, cairo_surface_get_device_scale};
+
+extern "C" {
+    fn cairo_surface_show_page(surface: *mut cairo_surface_t);
+}
 
 #[cfg(feature = "x11")]
 extern "C" {
@@ -52,10 +60,58 @@ type WaylandObjects = (egl::EGLDisplay, egl::EGLSurface, *mut wayland_sys::egl::
 #[cfg(all(feature = "x11", not(feature = "wayland")))]
 type WaylandObjects = ();
 
+/// The kinds of surface that are not attached to a window
+enum Offscreen {
+    Image,
+    Similar,
+    Document(DocumentFormat),
+}
+
 pub struct UnixCairoSurface {
     surface: *mut cairo_surface_t,
     size: (u32, u32),
     wayland_objects: Option<WaylandObjects>,
+    offscreen: Option<Offscreen>,
+    /// The number of pixels per point, set as the cairo device scale
+    scale: f64,
+}
+
+impl Drop for UnixCairoSurface {
+    fn drop(&mut self) {
+        unsafe {
+            cairo_surface_destroy(self.surface);
+        }
+    }
+}
+
+/// Create a surface like `other`, `w` by `h` pixels in size and with the same device scale
+fn create_similar_surface(other: *mut cairo_surface_t, w: u32, h: u32) -> Result<*mut cairo_surface_t, Box<dyn Error>> {
+    unsafe {
+        // cairo scales the size of similar surfaces by the device scale, so it's reset while
+        // creating one to keep the size exact
+        let (mut sx, mut sy) = (1.0, 1.0);
+        cairo_surface_get_device_scale(other, &mut sx, &mut sy);
+        cairo_surface_set_device_scale(other, 1.0, 1.0);
+        let surf = cairo_surface_create_similar(other, CONTENT_COLOR_ALPHA, w as i32, h as i32);
+        cairo_surface_set_device_scale(other, sx, sy);
+        cairo_surface_set_device_scale(surf, sx, sy);
+        if cairo_surface_status(surf) != STATUS_SUCCESS {
+            cairo_surface_destroy(surf);
+            return Err("failed to create similar cairo surface".into());
+        }
+        Ok(surf)
+    }
+}
+
+fn create_image_surface(w: u32, h: u32) -> Result<*mut cairo_surface_t, Box<dyn Error>> {
+    unsafe {
+        let surf = cairo_image_surface_create(FORMAT_A_RGB32, w as i32, h as i32);
+        if cairo_surface_status(surf) != STATUS_SUCCESS {
+            cairo_surface_destroy(surf);
+            return Err("failed to create cairo image surface".into());
+        }
+        Ok(surf)
+    }
 }
 
 impl cairo_context::CairoSurface for UnixCairoSurface {
@@ -123,7 +179,9 @@ impl cairo_context::CairoSurface for UnixCairoSurface {
                 Ok(UnixCairoSurface {
                     surface: cairo_gl_surface_create_for_egl(cdevice, egl_surf, width as i32, height as i32),
                     wayland_objects: Some((display, egl_surf, egl_window)),
-                    size: (width, height)
+                    offscreen: None,
+                    size: (width, height),
+                    scale: 1.0
                 })
             }
             #[cfg(not(feature = "wayland"))]
@@ -149,16 +207,60 @@ impl cairo_context::CairoSurface for UnixCairoSurface {
                 //println!("surf = {:?}", surf);
                 Ok(UnixCairoSurface { surface: surf,
                     wayland_objects: None,
-                    size: (w,h) })
+                    offscreen: None,
+                    size: (w,h),
+                    scale: 1.0 })
             }
         } else {
             Err("no window system found".into())
         }
     }
+    fn new_offscreen(w: u32, h: u32) -> Result<Self, Box<dyn Error>> where Self: Sized {
+        Ok(UnixCairoSurface {
+            surface: create_image_surface(w, h)?,
+            wayland_objects: None,
+            offscreen: Some(Offscreen::Image),
+            size: (w,h),
+            scale: 1.0
+        })
+    }
+    fn new_document(path: &Path, w: u32, h: u32, format: DocumentFormat) -> Result<Self, Box<dyn Error>> where Self: Sized {
+        let filename = CString::new(path.as_os_str().as_bytes())?;
+        unsafe {
+            let surf = match format {
+                DocumentFormat::Svg => cairo_svg_surface_create(filename.as_ptr(), w as f64, h as f64),
+                DocumentFormat::Pdf => cairo_pdf_surface_create(filename.as_ptr(), w as f64, h as f64),
+            };
+            if cairo_surface_status(surf) != STATUS_SUCCESS {
+                cairo_surface_destroy(surf);
+                return Err(format!("failed to create document {}", path.display()).into());
+            }
+            Ok(UnixCairoSurface {
+                surface: surf,
+                wayland_objects: None,
+                offscreen: Some(Offscreen::Document(format)),
+                size: (w,h),
+                scale: 1.0
+            })
+        }
+    }
+    fn new_similar(&self, w: u32, h: u32) -> Result<Self, Box<dyn Error>> where Self: Sized {
+        Ok(UnixCairoSurface {
+            surface: create_similar_surface(self.surface, w, h)?,
+            wayland_objects: None,
+            offscreen: Some(Offscreen::Similar),
+            size: (w,h),
+            scale: self.scale
+        })
+    }
     fn start_paint(&mut self) {
     }
     fn end_paint(&mut self) {
         unsafe {
+            if let Some(Offscreen::Document(_)) = self.offscreen {
+                // every paint is a new page, but cairo only keeps the first page of an SVG
+                cairo_surface_show_page(self.surface);
+            }
             cairo_surface_flush(self.surface);
             if self.wayland_objects.is_some() {
                 #[cfg(feature = "wayland")]
@@ -166,9 +268,25 @@ impl cairo_context::CairoSurface for UnixCairoSurface {
             }
         }
     }
-    fn resize(&mut self, w: u32, h: u32) {
-        self.size = (w,h);
-        if let Some(objs) = self.wayland_objects.as_ref() {
+    fn resize(&mut self, w: u32, h: u32) -> Result<(), Box<dyn Error>> {
+        if let Some(Offscreen::Image) = self.offscreen {
+            // image surfaces have a fixed size, so replace it with a new one
+            let surf = create_image_surface(w, h)?;
+            unsafe {
+                cairo_surface_destroy(self.surface);
+                cairo_surface_set_device_scale(surf, self.scale, self.scale);
+            }
+            self.surface = surf;
+        } else if let Some(Offscreen::Similar) = self.offscreen {
+            let surf = create_similar_surface(self.surface, w, h)?;
+            unsafe { cairo_surface_destroy(self.surface); }
+            self.surface = surf;
+        } else if let Some(Offscreen::Document(format)) = self.offscreen {
+            // only PDF supports pages of different sizes, starting with the next page
+            if format == DocumentFormat::Pdf {
+                unsafe { cairo_pdf_surface_set_size(self.surface, w as f64, h as f64); }
+            }
+        } else if let Some(objs) = self.wayland_objects.as_ref() {
             #[cfg(feature = "wayland")]
             unsafe {
                 (wayland_sys::egl::WAYLAND_EGL_HANDLE.wl_egl_window_resize)(
@@ -181,15 +299,31 @@ impl cairo_context::CairoSurface for UnixCairoSurface {
                 cairo_xlib_surface_set_size(self.surface, w as i32, h as i32);
             }
         }
-
+        self.size = (w,h);
+        Ok(())
     }
     fn surface(&self) -> *mut cairo_surface_t { self.surface }
-    fn bounds(&self) -> Rect { Rect::xywh(0.0, 0.0, self.size.0 as f32, self.size.1 as f32) }
-    fn pixels_to_points(&self, p: Point) -> Point { p }
+    fn bounds(&self) -> Rect {
+        Rect::xywh(0.0, 0.0, (self.size.0 as f64 / self.scale) as f32, (self.size.1 as f64 / self.scale) as f32)
+    }
+    fn pixels_to_points(&self, p: Point) -> Point {
+        Point::xy((p.x as f64 / self.scale) as f32, (p.y as f64 / self.scale) as f32)
+    }
+    fn scale(&self) -> f32 { self.scale as f32 }
+    fn set_scale(&mut self, scale: f32) {
+        self.scale = scale as f64;
+        unsafe { cairo_surface_set_device_scale(self.surface, self.scale, self.scale); }
+    }
+    // swapping GL buffers leaves the back buffer undefined
+    fn keeps_contents(&self) -> bool { self.wayland_objects.is_none() }
 }
 
+pub use cairo_context::{load_png, write_png};
+pub(crate) use cairo_context::{layout_id, image_id};
+
 pub type Font = cairo_context::Font;
 pub type TextLayout = cairo_context::TextLayout;
+pub type Image = cairo_context::Image;
 pub type RenderContext = cairo_context::CairoRenderContext<UnixCairoSurface>;
 
 pub fn init() { }
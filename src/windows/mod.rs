@@ -1,6 +1,9 @@
 use super::*;
 use std::mem::MaybeUninit;
+use vgu::IntoResult;
 use std::ptr::null_mut;
+use std::rc::Rc;
+use std::cell::Cell;
 
 mod vgu; //handle lowest level COM stuff
 
@@ -10,23 +13,309 @@ pub fn init() {
     }
 }
 
+pub fn load_png(_path: &std::path::Path) -> Result<PixelBuffer, Box<dyn Error>> {
+    Err("loading PNG files is not supported by the Direct2D backend yet".into())
+}
+
+pub fn write_png(_buf: &PixelBuffer, _path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    Err("writing PNG files is not supported by the Direct2D backend yet".into())
+}
+
 pub type Font = vgu::Com<vgu::IDWriteTextFormat>;
-pub type TextLayout = vgu::Com<vgu::IDWriteTextLayout>;
+
+/// A DirectWrite text layout. DirectWrite can't space out paragraphs, so each line is moved down by
+/// `paragraph_spacing` for every paragraph that starts above it
+#[derive(Clone)]
+pub struct TextLayout {
+    ly: vgu::Com<vgu::IDWriteTextLayout>,
+    paragraph_spacing: Rc<Cell<f32>>
+}
+
+impl std::ops::Deref for TextLayout {
+    type Target = vgu::Com<vgu::IDWriteTextLayout>;
+    fn deref(&self) -> &Self::Target {
+        &self.ly
+    }
+}
+
+impl TextLayout {
+    // the metrics of each line, with how far the paragraph spacing moves it down
+    unsafe fn line_metrics(&self) -> Vec<(vgu::DWRITE_LINE_METRICS, f32)> {
+        let mut count = 0;
+        (*self.p).GetLineMetrics(null_mut(), 0, &mut count);
+        let mut metrics = vec![std::mem::zeroed::<vgu::DWRITE_LINE_METRICS>(); count as usize];
+        if (*self.p).GetLineMetrics(metrics.as_mut_ptr(), count, &mut count) < 0 {
+            return Vec::new();
+        }
+        let spacing = self.paragraph_spacing.get();
+        let (mut offset, mut paragraph_end) = (0.0, false);
+        metrics.into_iter().map(|m| {
+            if paragraph_end {
+                offset += spacing;
+            }
+            paragraph_end = m.newlineLength > 0;
+            (m, offset)
+        }).collect()
+    }
+
+    // how far the paragraph spacing moves down the line holding the text at `pos`
+    unsafe fn offset_at(&self, pos: u32) -> f32 {
+        let mut start = 0;
+        let mut offset = 0.0;
+        for (m, o) in self.line_metrics() {
+            offset = o;
+            start += m.length;
+            if pos < start {
+                break;
+            }
+        }
+        offset
+    }
+}
+
+/// Identifies the text layout behind a handle, which all of its clones share
+pub(crate) fn layout_id(txl: &TextLayout) -> usize {
+    txl.p as usize
+}
+
+/// Identifies the bitmap behind a handle, which all of its clones share
+pub(crate) fn image_id(img: &Image) -> usize {
+    img.bmp.p as usize
+}
+
+fn d2d_point(p: Point) -> vgu::D2D1_POINT_2F {
+    vgu::D2D1_POINT_2F { x: p.x, y: p.y }
+}
+
+fn d2d_rounded_rect(rect: Rect, r: f32) -> vgu::D2D1_ROUNDED_RECT {
+    let r = r.max(0.0).min(rect.w.abs().min(rect.h.abs()) / 2.0);
+    vgu::D2D1_ROUNDED_RECT {
+        rect: vgu::D2D1_RECT_F{left: rect.x, top: rect.y, right: rect.x+rect.w, bottom: rect.y+rect.h},
+        radiusX: r, radiusY: r
+    }
+}
+
+/// Build a Direct2D path geometry with the same figures as `path`
+fn path_geometry(fac: &vgu::Factory, path: &Path) -> Result<vgu::PathGeometry, vgu::HResultError> {
+    use std::f32::consts::PI;
+    // Direct2D needs an explicit start for every figure, where Cairo starts them implicitly
+    unsafe fn begin(sink: &vgu::Com<vgu::ID2D1GeometrySink>, p: Point, in_figure: &mut bool, start: &mut Point) {
+        if !*in_figure {
+            sink.BeginFigure(d2d_point(p), vgu::D2D1_FIGURE_BEGIN_FILLED);
+            *in_figure = true;
+            *start = p;
+        }
+    }
+    let geo = vgu::PathGeometry::new(fac)?;
+    let sink = geo.open()?;
+    unsafe {
+        sink.SetFillMode(vgu::D2D1_FILL_MODE_WINDING);
+        let mut in_figure = false;
+        let mut start = Point::default();
+        let mut current = Point::default();
+        for seg in path.segments() {
+            match *seg {
+                PathSegment::MoveTo(p) => {
+                    if in_figure {
+                        sink.EndFigure(vgu::D2D1_FIGURE_END_OPEN);
+                        in_figure = false;
+                    }
+                    begin(&sink, p, &mut in_figure, &mut start);
+                    current = p;
+                },
+                PathSegment::LineTo(p) => {
+                    begin(&sink, current, &mut in_figure, &mut start);
+                    sink.AddLine(d2d_point(p));
+                    current = p;
+                },
+                PathSegment::QuadTo(c, p) => {
+                    begin(&sink, current, &mut in_figure, &mut start);
+                    sink.AddQuadraticBezier(&vgu::D2D1_QUADRATIC_BEZIER_SEGMENT { point1: d2d_point(c), point2: d2d_point(p) });
+                    current = p;
+                },
+                PathSegment::CubicTo(c1, c2, p) => {
+                    begin(&sink, current, &mut in_figure, &mut start);
+                    sink.AddBezier(&vgu::D2D1_BEZIER_SEGMENT { point1: d2d_point(c1), point2: d2d_point(c2), point3: d2d_point(p) });
+                    current = p;
+                },
+                PathSegment::Arc { center, radius, start_angle, end_angle } => {
+                    let at = |a: f32| Point::xy(center.x + radius * a.cos(), center.y + radius * a.sin());
+                    if in_figure {
+                        sink.AddLine(d2d_point(at(start_angle)));
+                    } else {
+                        begin(&sink, at(start_angle), &mut in_figure, &mut start);
+                    }
+                    // a single arc segment can't describe a full circle, so split the sweep in half
+                    let sweep = end_angle - start_angle;
+                    let steps = if sweep.abs() >= PI * 2.0 { 2 } else { 1 };
+                    let sweep = sweep.max(-PI * 2.0).min(PI * 2.0) / steps as f32;
+                    for i in 1..=steps {
+                        sink.AddArc(&vgu::D2D1_ARC_SEGMENT {
+                            point: d2d_point(at(start_angle + sweep * i as f32)),
+                            size: vgu::D2D1_SIZE_F { width: radius, height: radius },
+                            rotationAngle: 0.0,
+                            sweepDirection: if sweep >= 0.0 { vgu::D2D1_SWEEP_DIRECTION_CLOCKWISE } else { vgu::D2D1_SWEEP_DIRECTION_COUNTER_CLOCKWISE },
+                            arcSize: if sweep.abs() > PI { vgu::D2D1_ARC_SIZE_LARGE } else { vgu::D2D1_ARC_SIZE_SMALL },
+                        });
+                    }
+                    current = at(start_angle + sweep * steps as f32);
+                },
+                PathSegment::Close => {
+                    if in_figure {
+                        sink.EndFigure(vgu::D2D1_FIGURE_END_CLOSED);
+                        in_figure = false;
+                    }
+                    current = start;
+                },
+            }
+        }
+        if in_figure {
+            sink.EndFigure(vgu::D2D1_FIGURE_END_OPEN);
+        }
+        sink.Close().into_result(|| geo)
+    }
+}
 
 pub struct RenderContext {
+    d2fac: vgu::Factory,
     dwfac: vgu::TextFactory,
     rt: vgu::WindowRenderTarget,
     scb: vgu::Brush,
+    gradient: Option<vgu::Brush>,
+    stroke_style: Option<vgu::StrokeStyle>,
+    pushed: Vec<Pushed>,
+    transform: Affine,
+    saved_transforms: Vec<Affine>,
+    damaged: Option<Rect>,
     dpi: (f32, f32)
 }
 
+// layers and their masks have to stay alive until they are popped
+enum Pushed {
+    ClipRect,
+    ClipPath { _layer: vgu::Com<vgu::ID2D1Layer>, _mask: vgu::PathGeometry },
+    Layer { _layer: vgu::Com<vgu::ID2D1Layer> }
+}
+
+impl RenderContext {
+    fn push_d2d_layer(&mut self, mask: *mut vgu::ID2D1Geometry, opacity: f32) -> Option<vgu::Com<vgu::ID2D1Layer>> {
+        unsafe {
+            let mut layer: *mut vgu::ID2D1Layer = null_mut();
+            if self.rt.CreateLayer(std::ptr::null(), &mut layer) != vgu::S_OK {
+                return None;
+            }
+            let params = vgu::D2D1_LAYER_PARAMETERS {
+                contentBounds: vgu::D2D1_RECT_F{left: -f32::INFINITY, top: -f32::INFINITY, right: f32::INFINITY, bottom: f32::INFINITY},
+                geometricMask: mask,
+                maskAntialiasMode: vgu::D2D1_ANTIALIAS_MODE_PER_PRIMITIVE,
+                maskTransform: vgu::D2D1_MATRIX_3X2_F { matrix: [[1.0, 0.0], [0.0, 1.0], [0.0, 0.0]] },
+                opacity,
+                opacityBrush: null_mut(),
+                layerOptions: vgu::D2D1_LAYER_OPTIONS_NONE
+            };
+            self.rt.PushLayer(&params, layer);
+            Some(vgu::Com::from_ptr(layer))
+        }
+    }
+
+    fn pop_pushed(&mut self) {
+        unsafe {
+            match self.pushed.pop() {
+                Some(Pushed::ClipRect) => self.rt.PopAxisAlignedClip(),
+                Some(Pushed::ClipPath { .. }) | Some(Pushed::Layer { .. }) => self.rt.PopLayer(),
+                None => {}
+            }
+        }
+    }
+
+    fn brush(&self) -> *mut vgu::ID2D1Brush {
+        self.gradient.as_ref().unwrap_or(&self.scb).p
+    }
+
+    fn stroke_style(&self) -> *mut vgu::ID2D1StrokeStyle {
+        self.stroke_style.as_ref().map_or(null_mut(), |s| s.p)
+    }
+}
+
+impl FontExt for Font {
+    fn description(&self) -> FontDescription {
+        unsafe {
+            let len = self.GetFontFamilyNameLength();
+            let mut name = vec![0u16; len as usize + 1];
+            self.GetFontFamilyName(name.as_mut_ptr(), len + 1);
+            FontDescription {
+                name: String::from_utf16_lossy(&name[..len as usize]),
+                size: self.GetFontSize(),
+                weight: match self.GetFontWeight() {
+                    w if w <= vgu::DWRITE_FONT_WEIGHT_LIGHT => FontWeight::Light,
+                    w if w >= vgu::DWRITE_FONT_WEIGHT_SEMI_BOLD => FontWeight::Bold,
+                    _ => FontWeight::Regular
+                },
+                style: match self.GetFontStyle() {
+                    vgu::DWRITE_FONT_STYLE_NORMAL => FontStyle::Normal,
+                    _ => FontStyle::Italic
+                }
+            }
+        }
+    }
+
+    // text formats only name their font, so it is looked up again to measure it
+    fn metrics(&self) -> FontMetrics {
+        unsafe {
+            let mut collection: *mut vgu::IDWriteFontCollection = null_mut();
+            self.GetFontCollection(&mut collection);
+            if collection.is_null() {
+                let dwfac = match vgu::TextFactory::new() { Ok(f) => f, Err(_) => return FontMetrics::default() };
+                if (*dwfac.p).GetSystemFontCollection(&mut collection, 0) < 0 {
+                    return FontMetrics::default();
+                }
+            }
+            let collection = vgu::Com::from_ptr(collection);
+            let len = self.GetFontFamilyNameLength();
+            let mut name = vec![0u16; len as usize + 1];
+            self.GetFontFamilyName(name.as_mut_ptr(), len + 1);
+            let (mut index, mut exists) = (0, 0);
+            if (*collection.p).FindFamilyName(name.as_ptr(), &mut index, &mut exists) < 0 || exists == 0 {
+                return FontMetrics::default();
+            }
+            let mut family: *mut vgu::IDWriteFontFamily = null_mut();
+            if (*collection.p).GetFontFamily(index, &mut family) < 0 {
+                return FontMetrics::default();
+            }
+            let family = vgu::Com::from_ptr(family);
+            let mut font: *mut vgu::IDWriteFont = null_mut();
+            if (*family.p).GetFirstMatchingFont(self.GetFontWeight(), self.GetFontStretch(), self.GetFontStyle(), &mut font) < 0 {
+                return FontMetrics::default();
+            }
+            let font = vgu::Com::from_ptr(font);
+            let mut m: vgu::DWRITE_FONT_METRICS = std::mem::zeroed();
+            (*font.p).GetMetrics(&mut m);
+            let scale = self.GetFontSize() / m.designUnitsPerEm as f32;
+            FontMetrics { ascent: m.ascent as f32 * scale, descent: m.descent as f32 * scale, x_height: m.xHeight as f32 * scale }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Image {
+    bmp: vgu::Com<vgu::ID2D1Bitmap>,
+    size: (u32, u32)
+}
+
+impl ImageExt for Image {
+    fn bounds(&self) -> Rect {
+        Rect::wh(self.size.0 as f32, self.size.1 as f32)
+    }
+}
+
 impl TextLayoutExt for TextLayout {
     fn bounds(&self) -> Rect {
         unsafe {
             let mut metrics: MaybeUninit<vgu::DWRITE_TEXT_METRICS> = MaybeUninit::uninit();
             (*self.p).GetMetrics(metrics.as_mut_ptr());
             let metrics = metrics.assume_init();
-            Rect::xywh(metrics.left, metrics.top, metrics.width, metrics.height)
+            let spacing = self.line_metrics().last().map_or(0.0, |l| l.1);
+            Rect::xywh(metrics.left, metrics.top, metrics.width, metrics.height + spacing)
         }
     }
 
@@ -36,7 +325,30 @@ impl TextLayoutExt for TextLayout {
             let (mut x, mut y) = (0.0, 0.0);
             (*self.p).HitTestTextPosition(index as u32, 0, &mut x, &mut y, ht.as_mut_ptr());
             let ht = ht.assume_init();
-            Rect::xywh(x, y, ht.width, ht.height)
+            Rect::xywh(x, y + self.offset_at(index as u32), ht.width, ht.height)
+        }
+    }
+
+    fn lines(&self) -> Vec<LineMetrics> {
+        unsafe {
+            let (mut start, mut top) = (0, 0.0);
+            self.line_metrics().into_iter().map(|(m, offset)| {
+                // line metrics don't include where the line is across the layout, so its text is hit
+                // tested, which gives a rectangle for each run of the line
+                let len = m.length - m.newlineLength;
+                let mut hits = vec![std::mem::zeroed::<vgu::DWRITE_HIT_TEST_METRICS>(); len.max(1) as usize];
+                let mut n = 0;
+                (*self.p).HitTestTextRange(start, len, 0.0, 0.0, hits.as_mut_ptr(), hits.len() as u32, &mut n);
+                let hits = &hits[..(n as usize).min(hits.len())];
+                let x0 = hits.iter().map(|h| h.left).fold(f32::MAX, f32::min);
+                let x1 = hits.iter().map(|h| h.left + h.width).fold(f32::MIN, f32::max);
+                let y = top + offset;
+                let bounds = if hits.is_empty() { Rect::xywh(0.0, y, 0.0, m.height) } else { Rect::xywh(x0, y, x1 - x0, m.height) };
+                let line = LineMetrics { range: start as usize..(start + len) as usize, bounds, baseline: y + m.baseline };
+                start += m.length;
+                top += m.height;
+                line
+            }).collect()
         }
     }
 
@@ -45,10 +357,26 @@ impl TextLayoutExt for TextLayout {
             let mut ht: MaybeUninit<vgu::DWRITE_HIT_TEST_METRICS> = MaybeUninit::uninit();
             let mut inside:i32 = 0;
             let mut trailing:i32 = 0;
-            (*self.p).HitTestPoint(p.x, p.y, &mut trailing, &mut inside, ht.as_mut_ptr());
+            // undo the paragraph spacing of the line the point is on, counting the space above a
+            // paragraph as part of the line before it
+            let (mut y, mut offset) = (p.y, 0.0);
+            let lines = self.line_metrics();
+            let mut top = 0.0;
+            for (i, (m, o)) in lines.iter().enumerate() {
+                if top + o > p.y {
+                    break;
+                }
+                y = p.y - o;
+                if i + 1 < lines.len() {
+                    y = y.min(top + m.height - 0.5);
+                }
+                offset = *o;
+                top += m.height;
+            }
+            (*self.p).HitTestPoint(p.x, y, &mut trailing, &mut inside, ht.as_mut_ptr());
             let ht = ht.assume_init();
             if inside > 0 {
-                Some((ht.textPosition as usize, Rect::xywh(ht.left, ht.top, ht.width, ht.height)))
+                Some((ht.textPosition as usize, Rect::xywh(ht.left, ht.top + offset, ht.width, ht.height)))
             } else {
                 None
             }
@@ -97,6 +425,89 @@ impl TextLayoutExt for TextLayout {
             (*self.p).SetFontSize(size, r);
         }
     }
+
+    // DirectWrite leaves drawing the background of text to a custom renderer, which we don't have
+    fn background_range(&self, _range: Range<u32>, _col: Color) {}
+
+    fn family_range(&self, range: Range<u32>, family: &str) {
+        unsafe {
+            let r = vgu::DWRITE_TEXT_RANGE { startPosition: range.start, length: range.len() as u32 };
+            let mut name = family.encode_utf16().collect::<Vec<u16>>();
+            name.push(0u16);
+            (*self.p).SetFontFamilyName(name.as_ptr(), r);
+        }
+    }
+
+    fn set_wrap(&self, wrap: WrapMode) {
+        unsafe {
+            (*self.p).SetWordWrapping(match wrap {
+                WrapMode::Word => vgu::DWRITE_WORD_WRAPPING_WHOLE_WORD,
+                WrapMode::Char => vgu::DWRITE_WORD_WRAPPING_CHARACTER,
+                WrapMode::WordChar => vgu::DWRITE_WORD_WRAPPING_WRAP,
+                WrapMode::None => vgu::DWRITE_WORD_WRAPPING_NO_WRAP
+            });
+        }
+    }
+
+    // DirectWrite can only trim text at the end, so the start and middle are ellipsized there too
+    fn set_ellipsize(&self, mode: EllipsizeMode) {
+        unsafe {
+            if mode == EllipsizeMode::None {
+                let trimming = vgu::DWRITE_TRIMMING { granularity: vgu::DWRITE_TRIMMING_GRANULARITY_NONE, delimiter: 0, delimiterCount: 0 };
+                (*self.p).SetTrimming(&trimming, null_mut());
+                return;
+            }
+            let dwfac = match vgu::TextFactory::new() { Ok(f) => f, Err(_) => return };
+            let mut sign: *mut vgu::IDWriteInlineObject = null_mut();
+            if (*dwfac.p).CreateEllipsisTrimmingSign(self.p as *mut vgu::IDWriteTextFormat, &mut sign) < 0 {
+                return;
+            }
+            // the layout keeps its own reference to the sign
+            let sign = vgu::Com::from_ptr(sign);
+            let trimming = vgu::DWRITE_TRIMMING { granularity: vgu::DWRITE_TRIMMING_GRANULARITY_CHARACTER, delimiter: 0, delimiterCount: 0 };
+            (*self.p).SetTrimming(&trimming, sign.p);
+        }
+    }
+
+    // DirectWrite trims text to the layout's maximum height, so that is lowered to the bottom of the
+    // last line. The height the layout was created with isn't kept, so None leaves it as it is
+    fn set_max_lines(&self, lines: Option<u32>) {
+        let n = match lines { Some(n) => n as usize, None => return };
+        unsafe {
+            let mut count = 0;
+            (*self.p).GetLineMetrics(null_mut(), 0, &mut count);
+            let mut metrics = vec![std::mem::zeroed::<vgu::DWRITE_LINE_METRICS>(); count as usize];
+            if (*self.p).GetLineMetrics(metrics.as_mut_ptr(), count, &mut count) < 0 || metrics.len() <= n {
+                return;
+            }
+            let bottom: f32 = metrics[..n].iter().map(|m| m.height).sum();
+            (*self.p).SetMaxHeight(bottom.min((*self.p).GetMaxHeight()));
+        }
+    }
+
+    // DirectWrite can't indent the first line of a paragraph, and only has evenly spaced tab stops,
+    // so the first stop sets the spacing of all of them. Trimming doesn't take the paragraph spacing
+    // into account
+    fn set_paragraph_style(&self, style: &ParagraphStyle) {
+        self.paragraph_spacing.set(style.paragraph_spacing);
+        unsafe {
+            (*self.p).SetTextAlignment(match style.alignment {
+                TextAlignment::Left => vgu::DWRITE_TEXT_ALIGNMENT_LEADING,
+                TextAlignment::Center => vgu::DWRITE_TEXT_ALIGNMENT_CENTER,
+                TextAlignment::Right => vgu::DWRITE_TEXT_ALIGNMENT_TRAILING,
+                TextAlignment::Justify => vgu::DWRITE_TEXT_ALIGNMENT_JUSTIFIED
+            });
+            if style.line_spacing == 1.0 {
+                (*self.p).SetLineSpacing(vgu::DWRITE_LINE_SPACING_METHOD_DEFAULT, 0.0, 0.0);
+            } else {
+                (*self.p).SetLineSpacing(vgu::DWRITE_LINE_SPACING_METHOD_PROPORTIONAL, style.line_spacing, style.line_spacing);
+            }
+            // without any stops, tabs go back to DirectWrite's default of four times the font size
+            let mut size = 0.0;
+            (*self.p).GetFontSize(0, &mut size, null_mut());
+            (*self.p).SetIncrementalTabStop(style.tab_stops.first().copied().unwrap_or(4.0 * size));
+        }
+    }
 }
 
 use winit::platform::windows::WindowExtWindows;
@@ -125,7 +536,15 @@ impl RenderContextExt for RenderContext {
             (*rt.p).SetTextAntialiasMode(vgu::D2D1_TEXT_ANTIALIAS_MODE_CLEARTYPE);
         }
         let scb = vgu::Brush::solid_color(&rt, vgu::D2D1_COLOR_F{r:0.0,g:0.0,b:0.0,a:1.0})?;
-        Ok(RenderContext { dwfac, rt, scb, dpi })
+        Ok(RenderContext { d2fac, dwfac, rt, scb, gradient: None, stroke_style: None, pushed: Vec::new(), transform: Affine::identity(), saved_transforms: Vec::new(), damaged: None, dpi })
+    }
+
+    fn new_offscreen(_w: u32, _h: u32) -> Result<RenderContext, Box<dyn Error>> {
+        Err("offscreen rendering is not supported by the Direct2D backend yet".into())
+    }
+
+    fn new_document(_path: &std::path::Path, _w: u32, _h: u32, _format: DocumentFormat) -> Result<RenderContext, Box<dyn Error>> {
+        Err("document rendering is not supported by the Direct2D backend yet".into())
     }
 
     fn new_font(&self, name: &str, size: f32, weight: FontWeight, style: FontStyle) -> Result<Font, Box<dyn Error>> {
@@ -158,10 +577,19 @@ impl RenderContextExt for RenderContext {
             txd.push(0u16);
             txd.push(0u16);
             (*self.dwfac.p).CreateTextLayout(txd.as_ptr(), txd.len() as UINT32, f.p, width, height, lo.as_mut_ptr())
-                .into_result(|| Com::from_ptr(transmute(lo.assume_init()))).map_err(Into::into)
+                .into_result(|| TextLayout { ly: Com::from_ptr(transmute(lo.assume_init())), paragraph_spacing: Rc::new(Cell::new(0.0)) })
+                .map_err(Into::into)
         }
     }
 
+    fn new_attributed_text_layout(&self, text: &AttributedString, f: &Font, width: f32, height: f32) -> Result<TextLayout, Box<dyn Error>> {
+        let txl = self.new_text_layout(text.text(), f, width, height)?;
+        // DirectWrite ranges are in UTF-16 code units
+        let s = text.text();
+        apply_text_runs(&txl, text, |i| s[..i].encode_utf16().count() as u32, |range, col| txl.color_range(self, range, col));
+        Ok(txl)
+    }
+
 
     fn clear(&mut self, col: Color) {
         unsafe {
@@ -173,41 +601,234 @@ impl RenderContextExt for RenderContext {
         unsafe {
             self.scb.set_color(vgu::D2D1_COLOR_F{r:col.r, g:col.g, b:col.b, a:col.a});
         }
+        self.gradient = None;
+    }
+
+    fn set_brush(&mut self, brush: &Brush) {
+        fn d2d_stops(stops: &[GradientStop]) -> Vec<vgu::D2D1_GRADIENT_STOP> {
+            stops.iter().map(|s| vgu::D2D1_GRADIENT_STOP {
+                position: s.offset,
+                color: vgu::D2D1_COLOR_F{r: s.color.r, g: s.color.g, b: s.color.b, a: s.color.a}
+            }).collect()
+        }
+        fn d2d_extend(extend: ExtendMode) -> vgu::D2D1_EXTEND_MODE {
+            match extend {
+                ExtendMode::Pad => vgu::D2D1_EXTEND_MODE_CLAMP,
+                ExtendMode::Repeat => vgu::D2D1_EXTEND_MODE_WRAP,
+                ExtendMode::Reflect => vgu::D2D1_EXTEND_MODE_MIRROR
+            }
+        }
+        // if a gradient brush can't be created, drawing falls back to the last solid color
+        self.gradient = match *brush {
+            Brush::Solid(col) => return self.set_color(col),
+            Brush::LinearGradient { start, end, ref stops, extend } =>
+                vgu::Brush::linear_gradient(&self.rt,
+                    &vgu::D2D1_LINEAR_GRADIENT_BRUSH_PROPERTIES { startPoint: d2d_point(start), endPoint: d2d_point(end) },
+                    &d2d_stops(stops), d2d_extend(extend)).ok(),
+            Brush::RadialGradient { center, radius, ref stops, extend } =>
+                vgu::Brush::radial_gradient(&self.rt,
+                    &vgu::D2D1_RADIAL_GRADIENT_BRUSH_PROPERTIES {
+                        center: d2d_point(center), gradientOriginOffset: vgu::D2D1_POINT_2F{x: 0.0, y: 0.0},
+                        radiusX: radius, radiusY: radius
+                    },
+                    &d2d_stops(stops), d2d_extend(extend)).ok()
+        };
     }
 
     fn stroke_rect(&mut self, rect: Rect, stroke_width: f32) {
         unsafe {
             self.rt.DrawRectangle(&vgu::D2D1_RECT_F{left: rect.x, top: rect.y, right: rect.x+rect.w, bottom: rect.y+rect.h},
-                                  self.scb.p, stroke_width, std::ptr::null_mut());
+                                  self.brush(), stroke_width, self.stroke_style());
         }
     }
     fn fill_rect(&mut self, rect: Rect) {
         unsafe {
             self.rt.FillRectangle(&vgu::D2D1_RECT_F{left: rect.x, top: rect.y, right: rect.x+rect.w, bottom: rect.y+rect.h},
-                                  self.scb.p);
+                                  self.brush());
         }
     }
     fn draw_line(&mut self, a: Point, b: Point, stroke_width: f32) {
         unsafe {
             self.rt.DrawLine(vgu::D2D1_POINT_2F{x:a.x, y:a.y}, vgu::D2D1_POINT_2F{x:b.x, y:b.y},
-                             self.scb.p, stroke_width, std::ptr::null_mut());
+                             self.brush(), stroke_width, self.stroke_style());
         }
     }
+    fn set_stroke_style(&mut self, style: &StrokeStyle) {
+        let cap = match style.cap {
+            LineCap::Butt => vgu::D2D1_CAP_STYLE_FLAT,
+            LineCap::Round => vgu::D2D1_CAP_STYLE_ROUND,
+            LineCap::Square => vgu::D2D1_CAP_STYLE_SQUARE
+        };
+        let props = vgu::D2D1_STROKE_STYLE_PROPERTIES {
+            startCap: cap, endCap: cap, dashCap: cap,
+            lineJoin: match style.join {
+                LineJoin::Miter => vgu::D2D1_LINE_JOIN_MITER_OR_BEVEL,
+                LineJoin::Round => vgu::D2D1_LINE_JOIN_ROUND,
+                LineJoin::Bevel => vgu::D2D1_LINE_JOIN_BEVEL
+            },
+            miterLimit: style.miter_limit,
+            dashStyle: if style.is_dashed() { vgu::D2D1_DASH_STYLE_CUSTOM } else { vgu::D2D1_DASH_STYLE_SOLID },
+            dashOffset: style.dash_offset
+        };
+        // Direct2D already measures custom dashes in multiples of the stroke width
+        let dashes: &[f32] = if style.is_dashed() { &style.dashes } else { &[] };
+        // creation only fails for invalid properties, in which case strokes are left solid
+        self.stroke_style = vgu::StrokeStyle::new(&self.d2fac, &props, dashes).ok();
+    }
+
+    fn fill_path(&mut self, path: &Path) {
+        if let Ok(geo) = path_geometry(&self.d2fac, path) {
+            unsafe {
+                self.rt.FillGeometry(geo.p as *mut vgu::ID2D1Geometry, self.brush(), null_mut());
+            }
+        }
+    }
+    fn stroke_path(&mut self, path: &Path, stroke_width: f32) {
+        if let Ok(geo) = path_geometry(&self.d2fac, path) {
+            unsafe {
+                self.rt.DrawGeometry(geo.p as *mut vgu::ID2D1Geometry, self.brush(), stroke_width, self.stroke_style());
+            }
+        }
+    }
+    fn fill_rounded_rect(&mut self, rect: Rect, radii: CornerRadii) {
+        if radii.is_uniform() {
+            unsafe {
+                self.rt.FillRoundedRectangle(&d2d_rounded_rect(rect, radii.top_left), self.brush());
+            }
+        } else {
+            self.fill_path(&Path::rounded_rect(rect, radii));
+        }
+    }
+    fn stroke_rounded_rect(&mut self, rect: Rect, radii: CornerRadii, stroke_width: f32) {
+        if radii.is_uniform() {
+            unsafe {
+                self.rt.DrawRoundedRectangle(&d2d_rounded_rect(rect, radii.top_left), self.brush(), stroke_width, self.stroke_style());
+            }
+        } else {
+            self.stroke_path(&Path::rounded_rect(rect, radii), stroke_width);
+        }
+    }
+    fn fill_ellipse(&mut self, center: Point, rx: f32, ry: f32) {
+        unsafe {
+            self.rt.FillEllipse(&vgu::D2D1_ELLIPSE{point: d2d_point(center), radiusX: rx, radiusY: ry}, self.brush());
+        }
+    }
+    fn stroke_ellipse(&mut self, center: Point, rx: f32, ry: f32, stroke_width: f32) {
+        unsafe {
+            self.rt.DrawEllipse(&vgu::D2D1_ELLIPSE{point: d2d_point(center), radiusX: rx, radiusY: ry}, self.brush(), stroke_width, self.stroke_style());
+        }
+    }
+    fn draw_shadow(&mut self, shape: &Shape, offset: Point, blur_radius: f32, color: Color) {
+        // without Direct2D effects, the blur is approximated by layering rings of the outline that
+        // get wider and fainter, which fades the shadow out linearly
+        const RINGS: usize = 8;
+        let geo = match path_geometry(&self.d2fac, &shape.to_path()) { Ok(g) => g, Err(_) => return };
+        let ring_color = vgu::D2D1_COLOR_F{r: color.r, g: color.g, b: color.b, a: color.a / RINGS as f32};
+        let brush = match vgu::Brush::solid_color(&self.rt, ring_color) { Ok(b) => b, Err(_) => return };
+        let m = self.transform;
+        self.set_transform(&Affine::translate(offset).multiply(&m));
+        unsafe {
+            if blur_radius > 0.0 {
+                for i in 0..RINGS {
+                    let width = 2.0 * blur_radius * (RINGS - i) as f32 / RINGS as f32;
+                    self.rt.DrawGeometry(geo.p as *mut vgu::ID2D1Geometry, brush.p, width, null_mut());
+                }
+            }
+            brush.set_color(vgu::D2D1_COLOR_F{r: color.r, g: color.g, b: color.b, a: color.a});
+            self.rt.FillGeometry(geo.p as *mut vgu::ID2D1Geometry, brush.p, null_mut());
+        }
+        self.set_transform(&m);
+    }
+
     fn draw_text(&mut self, rect: Rect, s: &str, f: &Font) {
         unsafe {
             let s16 = s.encode_utf16().collect::<Vec<u16>>();
             self.rt.DrawText(s16.as_ptr(), s16.len() as u32,
-                f.p, &vgu::D2D1_RECT_F{left: rect.x, top: rect.y, right: rect.x+rect.w, bottom: rect.y+rect.h}, self.scb.p,
+                f.p, &vgu::D2D1_RECT_F{left: rect.x, top: rect.y, right: rect.x+rect.w, bottom: rect.y+rect.h}, self.brush(),
                 vgu::D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT, vgu::DWRITE_MEASURING_MODE_NATURAL);
         }
     }
     fn draw_text_layout(&mut self, p: Point, txl: &TextLayout) {
         unsafe {
-            self.rt.DrawTextLayout(vgu::D2D1_POINT_2F{x:p.x, y:p.y}, txl.p, self.scb.p,
-                                   vgu::D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT);
+            if txl.paragraph_spacing.get() == 0.0 {
+                self.rt.DrawTextLayout(vgu::D2D1_POINT_2F{x:p.x, y:p.y}, txl.p, self.brush(),
+                                       vgu::D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT);
+                return;
+            }
+            // the layout is drawn once for each paragraph, moved down by its spacing and clipped to
+            // its lines
+            let mut paragraphs: Vec<(f32, f32, f32)> = Vec::new();
+            let mut top = 0.0;
+            for (m, offset) in txl.line_metrics() {
+                match paragraphs.last_mut() {
+                    Some((_, bottom, o)) if *o == offset => *bottom += m.height,
+                    _ => paragraphs.push((top, top + m.height, offset))
+                }
+                top += m.height;
+            }
+            let bounds = txl.bounds();
+            for (top, bottom, offset) in paragraphs {
+                // glyphs can overhang the sides of the layout, so the clip is wider than it
+                let margin = bottom - top;
+                self.rt.PushAxisAlignedClip(&vgu::D2D1_RECT_F{left: p.x + bounds.x - margin, top: p.y + top + offset,
+                                                               right: p.x + bounds.x + bounds.w + margin, bottom: p.y + bottom + offset},
+                                            vgu::D2D1_ANTIALIAS_MODE_ALIASED);
+                self.rt.DrawTextLayout(vgu::D2D1_POINT_2F{x:p.x, y:p.y + offset}, txl.p, self.brush(),
+                                       vgu::D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT);
+                self.rt.PopAxisAlignedClip();
+            }
+        }
+    }
+
+    fn new_image(&self, pixels: &PixelBuffer) -> Result<Image, Box<dyn Error>> {
+        // Direct2D bitmaps are premultiplied BGRA
+        let mut data = Vec::with_capacity(pixels.width as usize * pixels.height as usize * 4);
+        for y in 0..pixels.height as usize {
+            let row = &pixels.data[y*pixels.stride .. y*pixels.stride + pixels.width as usize*4];
+            for px in row.chunks_exact(4) {
+                let a = px[3] as u32;
+                let premultiply = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+                data.extend_from_slice(&[premultiply(px[2]), premultiply(px[1]), premultiply(px[0]), px[3]]);
+            }
+        }
+        let props = vgu::D2D1_BITMAP_PROPERTIES {
+            pixelFormat: vgu::D2D1_PIXEL_FORMAT {
+                format: vgu::DXGI_FORMAT_B8G8R8A8_UNORM,
+                alphaMode: vgu::D2D1_ALPHA_MODE_PREMULTIPLIED
+            },
+            // at 96 DPI a pixel of the bitmap is one DIP, so source rectangles are in pixels
+            dpiX: 96.0, dpiY: 96.0
+        };
+        unsafe {
+            let mut bmp: *mut vgu::ID2D1Bitmap = null_mut();
+            self.rt.CreateBitmap(vgu::D2D_SIZE_U { width: pixels.width, height: pixels.height },
+                                 data.as_ptr() as *const _, pixels.width * 4, &props, &mut bmp)
+                .into_result(|| Image { bmp: vgu::Com::from_ptr(bmp), size: (pixels.width, pixels.height) })
+                .map_err(Into::into)
+        }
+    }
+
+    fn draw_image(&mut self, img: &Image, src: Rect, dst: Rect, opacity: f32, filter: ImageFilter) {
+        unsafe {
+            self.rt.DrawBitmap(img.bmp.p,
+                &vgu::D2D1_RECT_F{left: dst.x, top: dst.y, right: dst.x+dst.w, bottom: dst.y+dst.h},
+                opacity,
+                match filter {
+                    ImageFilter::Nearest => vgu::D2D1_BITMAP_INTERPOLATION_MODE_NEAREST_NEIGHBOR,
+                    ImageFilter::Linear => vgu::D2D1_BITMAP_INTERPOLATION_MODE_LINEAR
+                },
+                &vgu::D2D1_RECT_F{left: src.x, top: src.y, right: src.x+src.w, bottom: src.y+src.h});
         }
     }
 
+    fn new_render_target(&self, _w: u32, _h: u32) -> Result<RenderContext, Box<dyn Error>> {
+        Err("render targets are not supported by the Direct2D backend yet".into())
+    }
+
+    fn draw_render_target(&mut self, _target: &RenderContext, _m: &Affine, _opacity: f32) {
+        // there's no way to create a target to draw yet
+    }
+
     fn bounds(&self) -> Rect {
         unsafe {
             let s: vgu::D2D1_SIZE_F = (*self.rt.p).GetSize();
@@ -215,34 +836,142 @@ impl RenderContextExt for RenderContext {
         }
     }
 
+    fn read_pixels(&self) -> Result<PixelBuffer, Box<dyn Error>> {
+        Err("reading back pixels is not supported by the Direct2D backend yet".into())
+    }
+
+    fn write_png(&self, _path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        Err("writing PNG files is not supported by the Direct2D backend yet".into())
+    }
+
     fn translate(&mut self, p: Point) {
+        self.transform(&Affine::translate(p));
+    }
+
+    fn transform(&mut self, m: &Affine) {
+        let m = m.multiply(&self.transform);
+        self.set_transform(&m);
+    }
+
+    fn set_transform(&mut self, m: &Affine) {
+        self.transform = *m;
         unsafe {
             let s: vgu::D2D1_MATRIX_3X2_F = vgu::D2D1_MATRIX_3X2_F {
-                matrix: [ [1.0, 0.0],
-                          [0.0, 1.0],
-                          [p.x, p.y] ]
+                matrix: [ [m.a, m.b],
+                          [m.c, m.d],
+                          [m.e, m.f] ]
             };
             self.rt.SetTransform(&s);
         }
     }
 
+    fn current_transform(&self) -> Affine {
+        self.transform
+    }
+
+    fn save(&mut self) {
+        self.saved_transforms.push(self.transform);
+    }
+
+    fn restore(&mut self) {
+        if let Some(m) = self.saved_transforms.pop() {
+            self.set_transform(&m);
+        }
+    }
+
     fn pixels_to_points(&self, p: Point) -> Point {
         Point::xy(p.x * (96.0 / self.dpi.0), p.y * (96.0 / self.dpi.1))
     }
 
+    fn push_clip_rect(&mut self, rect: Rect) {
+        unsafe {
+            self.rt.PushAxisAlignedClip(&vgu::D2D1_RECT_F{left: rect.x, top: rect.y, right: rect.x+rect.w, bottom: rect.y+rect.h},
+                                        vgu::D2D1_ANTIALIAS_MODE_PER_PRIMITIVE);
+        }
+        self.pushed.push(Pushed::ClipRect);
+    }
+
+    fn push_clip_path(&mut self, path: &Path) {
+        let geo = match path_geometry(&self.d2fac, path) { Ok(g) => g, Err(_) => return };
+        if let Some(layer) = self.push_d2d_layer(geo.p as *mut vgu::ID2D1Geometry, 1.0) {
+            self.pushed.push(Pushed::ClipPath { _layer: layer, _mask: geo });
+        }
+    }
+
+    fn pop_clip(&mut self) {
+        match self.pushed.last() {
+            Some(Pushed::ClipRect) | Some(Pushed::ClipPath { .. }) => self.pop_pushed(),
+            _ => {}
+        }
+    }
+
+    // Direct2D render targets can only vary the opacity of layers, so every blend mode is drawn normally
+    fn push_layer(&mut self, opacity: f32, _blend: BlendMode) {
+        if let Some(layer) = self.push_d2d_layer(null_mut(), opacity) {
+            self.pushed.push(Pushed::Layer { _layer: layer });
+        }
+    }
+
+    fn pop_layer(&mut self) {
+        if let Some(i) = self.pushed.iter().rposition(|p| matches!(p, Pushed::Layer { .. })) {
+            while self.pushed.len() > i {
+                self.pop_pushed();
+            }
+        }
+    }
+
     fn start_paint(&mut self) {
+        self.pushed.clear();
+        self.saved_transforms.clear();
+        self.damaged = None;
         unsafe {
             self.rt.BeginDraw();
         }
+        self.set_transform(&Affine::identity());
+    }
+
+    fn start_paint_damaged(&mut self, damage: &Damage) {
+        self.start_paint();
+        if let Some(r) = damage.bounds() {
+            let r = r.round_out(self.scale_factor()).intersection(self.bounds()).unwrap_or(Rect::wh(0.0, 0.0));
+            // the clip is kept out of `pushed`, so popping clips can never remove it
+            unsafe {
+                self.rt.PushAxisAlignedClip(&vgu::D2D1_RECT_F{left: r.x, top: r.y, right: r.x+r.w, bottom: r.y+r.h},
+                                            vgu::D2D1_ANTIALIAS_MODE_ALIASED);
+            }
+            self.damaged = Some(r);
+        }
+    }
+
+    fn damaged_rect(&self) -> Rect {
+        self.damaged.unwrap_or_else(|| self.bounds())
     }
 
     fn end_paint(&mut self) {
+        // Direct2D requires every clip and layer to be popped before the frame ends
+        while !self.pushed.is_empty() {
+            self.pop_pushed();
+        }
         unsafe {
+            if self.damaged.is_some() {
+                self.rt.PopAxisAlignedClip();
+            }
             self.rt.EndDraw(null_mut(), null_mut());
         }
     }
 
-    fn resize(&mut self, w: u32, h: u32) {
-        self.rt.resize(w, h);
+    fn resize(&mut self, w: u32, h: u32) -> Result<(), Box<dyn Error>> {
+        self.rt.resize(w, h).map_err(Into::into)
+    }
+
+    fn scale_factor(&self) -> f32 {
+        self.dpi.0 / 96.0
+    }
+
+    fn set_scale_factor(&mut self, scale: f32) {
+        self.dpi = (scale * 96.0, scale * 96.0);
+        unsafe {
+            (*self.rt.p).SetDpi(self.dpi.0, self.dpi.1);
+        }
     }
 }
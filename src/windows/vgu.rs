@@ -15,6 +15,7 @@ pub use self::winapi::um::dwrite::*;
 pub use self::winapi::um::dcommon::{
     D2D_SIZE_U,
     D2D1_PIXEL_FORMAT,
+    D2D1_ALPHA_MODE_PREMULTIPLIED,
     DWRITE_MEASURING_MODE_NATURAL
 };  
 pub use self::winapi::shared::dxgiformat::*;
@@ -180,7 +181,8 @@ impl WindowRenderTarget {
         let hwnd_rp = D2D1_HWND_RENDER_TARGET_PROPERTIES {
             hwnd: win.hwnd() as HWND,
             pixelSize: size,
-            presentOptions: D2D1_PRESENT_OPTIONS_NONE
+            // keep the previous frame so damaged frames only need to redraw part of it
+            presentOptions: D2D1_PRESENT_OPTIONS_RETAIN_CONTENTS
         };
 
         let mut hrt: *mut ID2D1HwndRenderTarget = null_mut();
@@ -189,9 +191,9 @@ impl WindowRenderTarget {
         }
     }
 
-    pub fn resize(&mut self, w: u32, h: u32) {
+    pub fn resize(&mut self, w: u32, h: u32) -> Result<(), HResultError> {
         let rs = D2D_SIZE_U { width: w, height: h };
-        unsafe { self.Resize(&rs); }
+        unsafe { self.Resize(&rs).into_result(|| ()) }
     }
 }
 
@@ -208,6 +210,66 @@ impl Brush {
         let b: *mut ID2D1SolidColorBrush = transmute(self.p);
         (*b).SetColor(&col);
     }
+
+    pub fn linear_gradient(rt: &WindowRenderTarget, props: &D2D1_LINEAR_GRADIENT_BRUSH_PROPERTIES,
+                           stops: &[D2D1_GRADIENT_STOP], extend: D2D1_EXTEND_MODE) -> Result<Brush, HResultError> {
+        let stops = GradientStops::new(rt, stops, extend)?;
+        unsafe {
+            let mut brsh: *mut ID2D1LinearGradientBrush = null_mut();
+            (*rt.p).CreateLinearGradientBrush(props, null(), stops.p, &mut brsh).into_result(|| Com::from_ptr(transmute(brsh)))
+        }
+    }
+
+    pub fn radial_gradient(rt: &WindowRenderTarget, props: &D2D1_RADIAL_GRADIENT_BRUSH_PROPERTIES,
+                           stops: &[D2D1_GRADIENT_STOP], extend: D2D1_EXTEND_MODE) -> Result<Brush, HResultError> {
+        let stops = GradientStops::new(rt, stops, extend)?;
+        unsafe {
+            let mut brsh: *mut ID2D1RadialGradientBrush = null_mut();
+            (*rt.p).CreateRadialGradientBrush(props, null(), stops.p, &mut brsh).into_result(|| Com::from_ptr(transmute(brsh)))
+        }
+    }
+}
+
+pub type GradientStops = Com<ID2D1GradientStopCollection>;
+
+impl GradientStops {
+    pub fn new(rt: &WindowRenderTarget, stops: &[D2D1_GRADIENT_STOP], extend: D2D1_EXTEND_MODE) -> Result<GradientStops, HResultError> {
+        unsafe {
+            let mut coll: *mut ID2D1GradientStopCollection = null_mut();
+            (*rt.p).CreateGradientStopCollection(stops.as_ptr(), stops.len() as u32, D2D1_GAMMA_2_2, extend, &mut coll)
+                .into_result(|| Com::from_ptr(coll))
+        }
+    }
+}
+
+pub type PathGeometry = Com<ID2D1PathGeometry>;
+
+impl PathGeometry {
+    pub fn new(fac: &Factory) -> Result<PathGeometry, HResultError> {
+        unsafe {
+            let mut geo: *mut ID2D1PathGeometry = null_mut();
+            (*fac.p).CreatePathGeometry(&mut geo).into_result(|| Com::from_ptr(geo))
+        }
+    }
+
+    pub fn open(&self) -> Result<Com<ID2D1GeometrySink>, HResultError> {
+        unsafe {
+            let mut sink: *mut ID2D1GeometrySink = null_mut();
+            (*self.p).Open(&mut sink).into_result(|| Com::from_ptr(sink))
+        }
+    }
+}
+
+pub type StrokeStyle = Com<ID2D1StrokeStyle>;
+
+impl StrokeStyle {
+    pub fn new(fac: &Factory, props: &D2D1_STROKE_STYLE_PROPERTIES, dashes: &[f32]) -> Result<StrokeStyle, HResultError> {
+        unsafe {
+            let mut style: *mut ID2D1StrokeStyle = null_mut();
+            let dashes_ptr = if dashes.is_empty() { null() } else { dashes.as_ptr() };
+            (*fac.p).CreateStrokeStyle(props, dashes_ptr, dashes.len() as u32, &mut style).into_result(|| Com::from_ptr(style))
+        }
+    }
 }
 
 pub type TextFactory = Com<IDWriteFactory>;
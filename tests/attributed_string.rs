@@ -0,0 +1,126 @@
+use runic::*;
+use runic::display_list::DisplayList;
+
+fn red() -> Color { Color::rgb(1.0, 0.0, 0.0) }
+fn blue() -> Color { Color::rgb(0.0, 0.0, 1.0) }
+
+fn highlighted() -> AttributedString {
+    let mut s = AttributedString::new("");
+    s.push_str("fn", &[TextAttribute::Color(blue()), TextAttribute::Weight(FontWeight::Bold)]);
+    s.push_str(" ", &[]);
+    s.push_str("main", &[TextAttribute::Color(red())]);
+    s.push_str("() {}", &[]);
+    s
+}
+
+#[test]
+fn attributed_string() {
+    let mut s = highlighted();
+    assert_eq!(s.text(), "fn main() {}");
+    assert_eq!(s.runs(), &[
+        TextRun { range: 0..2, attribute: TextAttribute::Color(blue()) },
+        TextRun { range: 0..2, attribute: TextAttribute::Weight(FontWeight::Bold) },
+        TextRun { range: 3..7, attribute: TextAttribute::Color(red()) }
+    ]);
+    assert_eq!(s.attributes_at(4), vec![&TextAttribute::Color(red())]);
+    assert!(s.attributes_at(2).is_empty());
+
+    // setting an attribute replaces the same kind of attribute and joins up with equal runs
+    s.set_attribute(1..4, TextAttribute::Color(red()));
+    assert_eq!(s.runs()[0], TextRun { range: 0..1, attribute: TextAttribute::Color(blue()) });
+    assert!(s.runs().contains(&TextRun { range: 1..7, attribute: TextAttribute::Color(red()) }));
+    assert_eq!(s.attributes_at(1), vec![&TextAttribute::Weight(FontWeight::Bold), &TextAttribute::Color(red())]);
+
+    // inserting at the end of a run extends it, and moves the runs after it
+    let mut s = highlighted();
+    s.insert(7, "_loop");
+    assert_eq!(s.text(), "fn main_loop() {}");
+    assert_eq!(s.runs()[2], TextRun { range: 3..12, attribute: TextAttribute::Color(red()) });
+    s.insert(0, "pub ");
+    assert_eq!(s.runs()[0], TextRun { range: 4..6, attribute: TextAttribute::Color(blue()) });
+
+    // removing text shrinks runs and drops the ones it covers
+    s.remove(0..7);
+    assert_eq!(s.text(), "main_loop() {}");
+    assert_eq!(s.runs(), &[TextRun { range: 0..9, attribute: TextAttribute::Color(red()) }]);
+    s.replace_range(4..9, "");
+    s.clear_attributes(0..2);
+    assert_eq!(s.runs(), &[TextRun { range: 2..4, attribute: TextAttribute::Color(red()) }]);
+
+    let multibyte = AttributedString::new("😌 ok").with(5..7, TextAttribute::Underline(true));
+    assert_eq!(multibyte.attributes_at(5), vec![&TextAttribute::Underline(true)]);
+    assert!(AttributedString::with_runs("ok", &[TextRun { range: 1..3, attribute: TextAttribute::Size(9.0) }]).is_err());
+}
+
+#[test]
+fn attributed_text_layout() {
+    let mut rx = RenderContext::new_offscreen(200, 40).expect("create offscreen render context");
+    let font = rx.new_font("Arial", 24.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let text = AttributedString::new("MM MM")
+        .with(0..2, TextAttribute::Color(red()))
+        .with(3..5, TextAttribute::Background(blue()));
+    let txl = rx.new_attributed_text_layout(&text, &font, 200.0, 40.0).expect("create text layout");
+
+    // the same as setting the ranges on a plain layout
+    let plain = rx.new_text_layout(text.text(), &font, 200.0, 40.0).expect("create text layout");
+    plain.color_range(&rx, 0..2, red());
+    plain.background_range(3..5, blue());
+
+    let mut draw = |txl: &TextLayout| {
+        rx.start_paint();
+        rx.clear(Color::white());
+        rx.set_color(Color::black());
+        rx.draw_text_layout(Point::xy(0.0, 0.0), txl);
+        rx.end_paint();
+        rx.read_pixels().expect("read pixels")
+    };
+    let px = draw(&txl);
+    assert!(snapshot::compare(&px, &draw(&plain), 0).matches());
+
+    let first = txl.char_bounds(0);
+    let colors = |r: Rect| {
+        let mut found = Vec::new();
+        for y in r.y as u32..(r.y + r.h) as u32 {
+            for x in r.x as u32..(r.x + r.w) as u32 {
+                found.push(px.pixel(x, y));
+            }
+        }
+        found
+    };
+    assert!(colors(first).iter().any(|p| p[0] > 200 && p[1] < 60 && p[2] < 60));
+    // none of it is drawn in the black of the brush
+    assert!(colors(first).iter().all(|p| p[0] > 200));
+    // the background fills the whole box of the character, not just the glyph
+    let last = txl.char_bounds(4);
+    assert!(colors(last).iter().filter(|p| **p == [0, 0, 255, 255]).count() > colors(last).len() / 3);
+}
+
+#[test]
+fn display_list_attributed_text() {
+    let mut list = DisplayList::new(200, 40).expect("create display list");
+    let font = list.new_font("Arial", 24.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let txl = list.new_attributed_text_layout(&highlighted(), &font, 200.0, 40.0).expect("create text layout");
+    list.start_paint();
+    list.clear(Color::white());
+    list.draw_text_layout(Point::xy(0.0, 0.0), &txl);
+    list.end_paint();
+    let source = list.layout_source(0).expect("layout source");
+    assert_eq!(source.text, "fn main() {}");
+    assert_eq!(source.runs, highlighted().runs());
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&highlighted()).expect("serialize attributed string");
+        let loaded: AttributedString = serde_json::from_str(&json).expect("deserialize attributed string");
+        assert_eq!(loaded, highlighted());
+        let bad = r#"{"text":"ok","runs":[{"range":{"start":1,"end":3},"attribute":{"Underline":true}}]}"#;
+        assert!(serde_json::from_str::<AttributedString>(bad).is_err());
+
+        // replaying a loaded list creates the layout again with its runs
+        let json = serde_json::to_string(&list).expect("serialize display list");
+        let loaded: DisplayList = serde_json::from_str(&json).expect("deserialize display list");
+        assert_eq!(loaded.layout_source(0), list.layout_source(0));
+        let cmp = snapshot::compare(&loaded.read_pixels().expect("read pixels"), &list.read_pixels().expect("read pixels"), 0);
+        assert!(cmp.matches());
+    }
+}
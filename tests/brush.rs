@@ -0,0 +1,39 @@
+use runic::*;
+
+#[test]
+fn brush() {
+    let red = Color::rgb(1.0, 0.0, 0.0);
+    let blue = Color::rgb(0.0, 0.0, 1.0);
+    let mut rx = RenderContext::new_offscreen(200, 100).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_brush(&Brush::linear(Point::xy(0.0, 0.0), Point::xy(100.0, 0.0),
+        &[GradientStop::new(0.0, red), GradientStop::new(1.0, blue)]));
+    rx.fill_rect(Rect::xywh(0.0, 0.0, 100.0, 40.0));
+    rx.set_brush(&Brush::LinearGradient {
+        start: Point::xy(0.0, 0.0), end: Point::xy(50.0, 0.0),
+        stops: vec![GradientStop::new(0.0, red), GradientStop::new(1.0, blue)],
+        extend: ExtendMode::Repeat
+    });
+    rx.draw_line(Point::xy(0.0, 50.0), Point::xy(100.0, 50.0), 10.0);
+    rx.set_brush(&Brush::radial(Point::xy(150.0, 50.0), 40.0,
+        &[GradientStop::new(0.0, Color::black()), GradientStop::new(1.0, Color::white().with_alpha(0.0))]));
+    rx.fill_rect(Rect::xywh(100.0, 0.0, 100.0, 100.0));
+    rx.set_color(Color::black());
+    rx.fill_rect(Rect::xywh(0.0, 80.0, 20.0, 20.0));
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    snapshot::assert_pixel(&px, 0, 20, [255, 0, 0, 255], 12);
+    snapshot::assert_pixel(&px, 50, 20, [128, 0, 127, 255], 12);
+    snapshot::assert_pixel(&px, 99, 20, [0, 0, 255, 255], 12);
+    // the repeating gradient starts over halfway along the line
+    snapshot::assert_pixel(&px, 49, 50, [0, 0, 255, 255], 12);
+    snapshot::assert_pixel(&px, 50, 50, [255, 0, 0, 255], 12);
+    // the radial gradient fades out from its center and is padded past its radius
+    snapshot::assert_pixel(&px, 150, 50, [0, 0, 0, 255], 12);
+    snapshot::assert_pixel(&px, 170, 50, [191, 191, 191, 255], 12);
+    assert_eq!(px.pixel(198, 50), [255, 255, 255, 255]);
+    // set_color replaces the brush
+    assert_eq!(px.pixel(10, 90), [0, 0, 0, 255]);
+}
@@ -0,0 +1,47 @@
+use runic::*;
+
+#[test]
+fn clip() {
+    let mut rx = RenderContext::new_offscreen(100, 100).expect("create offscreen render context");
+    let mut triangle = Path::new();
+    triangle.move_to(Point::xy(0.0, 0.0)).line_to(Point::xy(100.0, 0.0)).line_to(Point::xy(0.0, 100.0)).close();
+
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.push_clip_rect(Rect::xywh(10.0, 10.0, 60.0, 60.0));
+    rx.push_clip_path(&triangle);
+    rx.set_color(Color::rgb(1.0, 0.0, 0.0));
+    rx.fill_rect(Rect::wh(100.0, 100.0));
+    rx.pop_clip();
+    // the brush set while the clip was pushed is kept after it is popped
+    rx.fill_rect(Rect::xywh(60.0, 60.0, 20.0, 20.0));
+    rx.pop_clip();
+    rx.push_clip_rect(Rect::xywh(90.0, 90.0, 10.0, 10.0));
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    // inside both clips, inside only the rectangle, and inside only the triangle
+    assert_eq!(px.pixel(20, 20), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(60, 50), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(5, 5), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(65, 65), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(75, 75), [255, 255, 255, 255]);
+
+    // clips left pushed at the end of a frame are removed by the next start_paint
+    rx.start_paint();
+    rx.set_color(Color::black());
+    rx.fill_rect(Rect::wh(100.0, 100.0));
+    rx.end_paint();
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(5, 5), [0, 0, 0, 255]);
+
+    // popping with no clip pushed does nothing
+    rx.start_paint();
+    rx.pop_clip();
+    rx.set_color(Color::white());
+    rx.fill_rect(Rect::wh(100.0, 100.0));
+    rx.end_paint();
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(5, 5), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(95, 95), [255, 255, 255, 255]);
+}
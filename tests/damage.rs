@@ -0,0 +1,122 @@
+use runic::*;
+use runic::driver::Driver;
+use runic::display_list::{Command, DisplayList};
+
+struct Caret {
+    visible: bool,
+    background: Color,
+    painted: Vec<Rect>
+}
+
+const CARET: Rect = Rect { x: 20.5, y: 10.0, w: 2.0, h: 20.0 };
+
+impl App for Caret {
+    fn init(_: &mut RenderContext) -> Self {
+        Caret { visible: true, background: Color::white(), painted: Vec::new() }
+    }
+
+    fn paint(&mut self, rx: &mut RenderContext) {
+        self.painted.push(rx.damaged_rect());
+        rx.clear(self.background);
+        if self.visible {
+            rx.set_color(Color::black());
+            rx.fill_rect(CARET);
+        }
+    }
+
+    fn event(&mut self, _: Event, _: &mut ControlFlowOpts, _: &mut bool) {}
+
+    fn event_with_damage(&mut self, e: Event, _: &mut ControlFlowOpts, damage: &mut Damage) {
+        match e {
+            Event::ReceivedCharacter('b') => {
+                self.visible = !self.visible;
+                // the background changes too, but only the caret is repainted
+                self.background = Color::rgb(1.0, 0.0, 0.0);
+                damage.add(CARET);
+            },
+            Event::ReceivedCharacter('r') => {
+                self.background = Color::rgb(0.0, 0.0, 1.0);
+                damage.add_all();
+            },
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn damage() {
+    let mut d = Damage::new();
+    assert!(d.is_empty() && d.bounds().is_none());
+    d.add(Rect::xywh(0.0, 0.0, 10.0, 10.0));
+    d.add(Rect::xywh(20.0, 5.0, 10.0, 10.0));
+    d.add(Rect::xywh(50.0, 50.0, 0.0, 10.0));
+    assert_eq!(d.rects().len(), 2);
+    assert_eq!(d.bounds(), Some(Rect::xywh(0.0, 0.0, 30.0, 15.0)));
+    d.add_all();
+    assert!(d.is_all() && d.bounds().is_none());
+    d.clear();
+    assert!(d.is_empty());
+
+    let mut driver = Driver::<Caret>::new(60, 40).expect("create driver");
+    driver.received_character('b');
+    assert_eq!(driver.frame_count(), 2);
+    // the damage is rounded out to whole pixels
+    assert_eq!(driver.app.painted[1], Rect::xywh(20.0, 10.0, 3.0, 20.0));
+    let px = driver.frame();
+    assert_eq!(px.pixel(21, 20), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(5, 5), [255, 255, 255, 255]);
+
+    // events that damage nothing don't paint
+    driver.received_character('x');
+    assert_eq!(driver.frame_count(), 2);
+
+    driver.received_character('r');
+    assert_eq!(driver.app.painted[2], Rect::wh(60.0, 40.0));
+    assert_eq!(driver.frame().pixel(5, 5), [0, 0, 255, 255]);
+}
+
+fn paint_damaged<R: RenderContextExt>(rx: &mut R) {
+    let mut damage = Damage::new();
+    damage.add(Rect::xywh(10.0, 10.0, 20.0, 20.0));
+    rx.start_paint_damaged(&damage);
+    // popping a clip that wasn't pushed doesn't remove the damage clip
+    rx.pop_clip();
+    rx.push_clip_rect(Rect::xywh(0.0, 0.0, 20.0, 40.0));
+    rx.pop_clip();
+    rx.pop_clip();
+    rx.set_color(Color::rgb(1.0, 0.0, 0.0));
+    rx.fill_rect(Rect::wh(60.0, 40.0));
+    rx.end_paint();
+}
+
+#[test]
+fn damage_clip() {
+    let mut rx = RenderContext::new_offscreen(60, 40).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.end_paint();
+    paint_damaged(&mut rx);
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(20, 20), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(5, 5), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(40, 20), [255, 255, 255, 255]);
+
+    // the next frame isn't clipped to the last one's damage
+    rx.start_paint();
+    rx.fill_rect(Rect::wh(60.0, 40.0));
+    rx.end_paint();
+    assert_eq!(rx.read_pixels().expect("read pixels").pixel(5, 5), [255, 0, 0, 255]);
+
+    // a display list keeps the damage clip apart from its commands
+    let mut list = DisplayList::new(60, 40).expect("create display list");
+    paint_damaged(&mut list);
+    assert!(!list.commands().contains(&Command::PushClipRect(Rect::xywh(10.0, 10.0, 20.0, 20.0))));
+    let mut rx = RenderContext::new_offscreen(60, 40).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::white());
+    list.replay(&mut rx);
+    rx.end_paint();
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(20, 20), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(5, 5), [255, 255, 255, 255]);
+}
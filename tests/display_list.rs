@@ -0,0 +1,96 @@
+use runic::*;
+use runic::display_list::{Command, DisplayList};
+
+fn draw<R: RenderContextExt>(rx: &mut R, x: f32, font: &Font, txl: &TextLayout, img: &Image) {
+    rx.clear(Color::white());
+    rx.set_color(Color::rgb(0.0, 0.0, 1.0));
+    rx.fill_rect(Rect::xywh(x, 10.0, 20.0, 20.0));
+    rx.save();
+    rx.translate(Point::xy(100.0, 0.0));
+    rx.stroke_ellipse(Point::xy(20.0, 20.0), 10.0, 10.0, 2.0);
+    rx.restore();
+    rx.push_layer(0.5, BlendMode::Normal);
+    rx.draw_image(img, img.bounds(), Rect::xywh(150.0, 10.0, 20.0, 20.0), 1.0, ImageFilter::Nearest);
+    rx.pop_layer();
+    rx.draw_text(Rect::xywh(0.0, 40.0, 200.0, 30.0), "Recorded", font);
+    rx.draw_text_layout(Point::xy(0.0, 70.0), txl);
+}
+
+fn frame(list: &mut DisplayList, x: f32, pixels: &PixelBuffer) {
+    let font = list.new_font("Arial", 16.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let txl = list.new_text_layout("Laid out", &font, 200.0, 30.0).expect("create text layout");
+    let img = list.new_image(pixels).expect("create image");
+    list.start_paint();
+    draw(list, x, &font, &txl, &img);
+    list.end_paint();
+}
+
+#[test]
+fn display_list() {
+    let mut pixels = PixelBuffer::new(1, 1);
+    pixels.data.copy_from_slice(&[255, 0, 0, 255]);
+
+    let mut list = DisplayList::new(200, 100).expect("create display list");
+    frame(&mut list, 10.0, &pixels);
+    assert_eq!(list.commands().len(), 12);
+    assert_eq!(list.commands()[2], Command::FillRect(Rect::xywh(10.0, 10.0, 20.0, 20.0)));
+    assert_eq!(list.current_transform(), Affine::identity());
+    assert_eq!(list.layout_source(0).map(|s| s.text.as_str()), Some("Laid out"));
+    assert_eq!(list.image_pixels(0), Some(&pixels));
+
+    // replaying draws the same thing as drawing directly
+    let mut rx = RenderContext::new_offscreen(200, 100).expect("create offscreen render context");
+    let font = rx.new_font("Arial", 16.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let txl = rx.new_text_layout("Laid out", &font, 200.0, 30.0).expect("create text layout");
+    let img = rx.new_image(&pixels).expect("create image");
+    rx.start_paint();
+    draw(&mut rx, 10.0, &font, &txl, &img);
+    rx.end_paint();
+    let direct = rx.read_pixels().expect("read pixels");
+    assert!(snapshot::compare(&list.read_pixels().expect("read pixels"), &direct, 2).matches());
+
+    // only the moved square differs between frames
+    let mut moved = DisplayList::new(200, 100).expect("create display list");
+    frame(&mut moved, 30.0, &pixels);
+    let r = list.changed_region(&moved).expect("changed region");
+    assert!(r.x <= 10.0 && r.y <= 10.0 && r.x + r.w >= 50.0 && r.y + r.h >= 30.0);
+    assert!(r.x >= 8.0 && r.x + r.w <= 52.0 && r.h <= 24.0);
+
+    let mut same = DisplayList::new(200, 100).expect("create display list");
+    frame(&mut same, 10.0, &pixels);
+    assert_eq!(list.changed_region(&same), None);
+
+    // a different brush changes everything drawn with it
+    same.start_paint();
+    same.set_color(Color::rgb(0.0, 1.0, 0.0));
+    same.fill_rect(Rect::xywh(10.0, 10.0, 20.0, 20.0));
+    same.fill_rect(Rect::xywh(60.0, 10.0, 20.0, 20.0));
+    let mut other = DisplayList::new(200, 100).expect("create display list");
+    other.start_paint();
+    other.set_color(Color::rgb(0.0, 0.0, 1.0));
+    other.fill_rect(Rect::xywh(10.0, 10.0, 20.0, 20.0));
+    other.fill_rect(Rect::xywh(60.0, 10.0, 20.0, 20.0));
+    let r = same.changed_region(&other).expect("changed region");
+    assert!(r.x <= 10.0 && r.x + r.w >= 80.0);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn display_list_serde() {
+    let mut pixels = PixelBuffer::new(1, 1);
+    pixels.data.copy_from_slice(&[255, 0, 0, 255]);
+    let mut list = DisplayList::new(200, 100).expect("create display list");
+    frame(&mut list, 10.0, &pixels);
+
+    let json = serde_json::to_string(&list).expect("serialize display list");
+    let loaded: DisplayList = serde_json::from_str(&json).expect("deserialize display list");
+    assert_eq!(loaded.commands(), list.commands());
+    assert_eq!(loaded.layout_source(0), list.layout_source(0));
+    let cmp = snapshot::compare(&loaded.read_pixels().expect("read pixels"), &list.read_pixels().expect("read pixels"), 2);
+    assert!(cmp.matches());
+
+    // loaded layouts and images are compared by their contents
+    let again: DisplayList = serde_json::from_str(&json).expect("deserialize display list");
+    assert_eq!(loaded.changed_region(&again), None);
+    assert_eq!(loaded.changed_region(&list), None);
+}
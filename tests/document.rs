@@ -0,0 +1,42 @@
+use runic::*;
+
+fn paint_page(rx: &mut RenderContext, font: &Font, page: usize) {
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::rgb(0.4, 0.05, 1.0));
+    rx.stroke_rect(Rect::xywh(36.0, 36.0, 200.0, 100.0), 2.0);
+    rx.draw_line(Point::xy(36.0, 36.0), Point::xy(236.0, 136.0), 1.0);
+    rx.set_color(Color::black());
+    rx.draw_text(Rect::xywh(36.0, 160.0, 300.0, 40.0), &format!("Page {}", page), font);
+    rx.end_paint();
+}
+
+#[test]
+fn document() {
+    let dir = std::env::temp_dir();
+
+    let pdf_path = dir.join(format!("runic-document-test-{}.pdf", std::process::id()));
+    {
+        let mut rx = RenderContext::new_document(&pdf_path, 612, 792, DocumentFormat::Pdf).expect("create PDF");
+        let font = rx.new_font("Arial", 18.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+        assert_eq!((rx.bounds().w, rx.bounds().h), (612.0, 792.0));
+        paint_page(&mut rx, &font, 1);
+        paint_page(&mut rx, &font, 2);
+    }
+    let pdf = std::fs::read(&pdf_path).expect("read PDF");
+    assert!(pdf.starts_with(b"%PDF"));
+    std::fs::remove_file(&pdf_path).expect("remove PDF");
+    let pdf = String::from_utf8_lossy(&pdf);
+    assert!(pdf.contains("/Count 2"));
+
+    let svg_path = dir.join(format!("runic-document-test-{}.svg", std::process::id()));
+    {
+        let mut rx = RenderContext::new_document(&svg_path, 400, 300, DocumentFormat::Svg).expect("create SVG");
+        let font = rx.new_font("Arial", 18.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+        paint_page(&mut rx, &font, 1);
+    }
+    let svg = std::fs::read_to_string(&svg_path).expect("read SVG");
+    std::fs::remove_file(&svg_path).expect("remove SVG");
+    assert!(svg.contains("<svg"));
+    assert!(svg.contains("<path"));
+}
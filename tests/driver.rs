@@ -0,0 +1,87 @@
+use runic::*;
+use runic::driver::Driver;
+
+struct TestApp {
+    mouse_loc: Point,
+    mouse_button: Option<MouseButton>,
+    typed: String,
+}
+
+impl App for TestApp {
+    fn init(_: &mut RenderContext) -> Self {
+        TestApp { mouse_loc: Point::xy(0.0,0.0), mouse_button: None, typed: String::new() }
+    }
+
+    fn paint(&mut self, rx: &mut RenderContext) {
+        rx.clear(Color::rgb(1.0, 1.0, 1.0));
+        rx.set_color(self.mouse_button.map_or(Color::rgb(0.0, 0.0, 0.0), |v| match v {
+            MouseButton::Left => Color::rgb(1.0, 0.0, 0.0),
+            _ => Color::rgb(0.0, 0.0, 1.0)
+        }));
+        rx.fill_rect(Rect::pnwh(self.mouse_loc, 8.0, 8.0));
+    }
+
+    fn event(&mut self, e: Event, elf: &mut ControlFlowOpts, should_redraw: &mut bool) {
+        match e {
+            Event::CloseRequested => *elf = ControlFlowOpts::Exit,
+            Event::CursorMoved { position: dpi::PhysicalPosition { x,y }, .. } => {
+                self.mouse_loc = Point::xy(x as f32,y as f32);
+                *should_redraw = true;
+            },
+            Event::MouseInput { state, button, .. } => {
+                self.mouse_button = match state {
+                    ElementState::Pressed => Some(button),
+                    _ => None
+                };
+                *should_redraw = true;
+            },
+            Event::ReceivedCharacter(c) => self.typed.push(c),
+            Event::KeyboardInput { input, .. } if input.virtual_keycode == Some(VirtualKeyCode::Escape) => {
+                *elf = ControlFlowOpts::Exit;
+            },
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn driver() {
+    let mut d = Driver::<TestApp>::new(64, 64).expect("create driver");
+    assert_eq!(d.frame_count(), 1);
+    assert_eq!(d.frame().pixel(4, 4), [0, 0, 0, 255]);
+
+    d.cursor_moved(Point::xy(20.0, 30.0));
+    assert_eq!(d.frame_count(), 2);
+    assert_eq!((d.app.mouse_loc.x, d.app.mouse_loc.y), (20.0, 30.0));
+    assert_eq!(d.frame().pixel(24, 34), [0, 0, 0, 255]);
+
+    d.mouse_input(MouseButton::Left, ElementState::Pressed);
+    assert_eq!(d.frame().pixel(24, 34), [255, 0, 0, 255]);
+    d.mouse_input(MouseButton::Left, ElementState::Released);
+    assert_eq!(d.app.mouse_button, None);
+    assert_eq!(d.frame_count(), 4);
+
+    // typing doesn't ask for a redraw
+    d.received_character('h').received_character('i');
+    assert_eq!(d.app.typed, "hi");
+    assert_eq!(d.frame_count(), 4);
+
+    // resizing always repaints
+    d.resize(32, 48);
+    assert_eq!(d.frame_count(), 5);
+    assert_eq!((d.frame().width, d.frame().height), (32, 48));
+    assert_eq!(d.control_flow(), ControlFlowOpts::Wait);
+
+    d.key_press(VirtualKeyCode::Escape);
+    assert!(d.exited());
+    d.cursor_moved(Point::xy(0.0, 0.0));
+    assert_eq!((d.app.mouse_loc.x, d.app.mouse_loc.y), (20.0, 30.0));
+}
+
+#[test]
+fn driver_close() {
+    let mut d = Driver::<TestApp>::new(16, 16).expect("create driver");
+    d.close();
+    assert!(d.exited());
+    assert_eq!(d.frame_count(), 1);
+}
@@ -0,0 +1,103 @@
+use runic::*;
+use runic::driver::Driver;
+use runic::display_list::DisplayList;
+
+fn draw_at(scale: f32) -> (RenderContext, PixelBuffer) {
+    let mut rx = RenderContext::new_offscreen(120, 90).expect("create offscreen render context");
+    rx.set_scale_factor(scale);
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::black());
+    rx.fill_rect(Rect::xywh(10.0, 10.0, 20.0, 20.0));
+    rx.draw_shadow(&Rect::xywh(40.0, 10.0, 10.0, 10.0).into(), Point::xy(2.0, 2.0), 0.0, Color::rgb(1.0, 0.0, 0.0));
+    rx.end_paint();
+    let px = rx.read_pixels().expect("read pixels");
+    (rx, px)
+}
+
+#[test]
+fn scaled_drawing() {
+    for &scale in [1.0f32, 1.5, 2.0].iter() {
+        let (rx, px) = draw_at(scale);
+        assert_eq!(rx.scale_factor(), scale);
+        // the size stays the same in pixels, so there are fewer points
+        assert_eq!(rx.bounds(), Rect::wh(120.0 / scale, 90.0 / scale));
+        let p = rx.pixels_to_points(Point::xy(60.0, 30.0));
+        assert_eq!((p.x, p.y), (60.0 / scale, 30.0 / scale));
+        // the transform is in points, so it doesn't include the scale factor
+        assert_eq!(rx.current_transform(), Affine::identity());
+
+        // the square covers 20 points, which is 20 * scale pixels
+        let px_at = |x: f32, y: f32| px.pixel((x * scale) as u32, (y * scale) as u32);
+        assert_eq!(px_at(10.5, 10.5), [0, 0, 0, 255], "at {}x", scale);
+        assert_eq!(px_at(29.5, 29.5), [0, 0, 0, 255], "at {}x", scale);
+        assert_eq!(px_at(30.5, 20.0), [255, 255, 255, 255], "at {}x", scale);
+        assert_eq!(px_at(9.5, 20.0), [255, 255, 255, 255], "at {}x", scale);
+
+        // the shadow is offset and sized in points as well
+        assert_eq!(px_at(51.0, 21.0), [255, 0, 0, 255], "at {}x", scale);
+        assert_eq!(px_at(53.0, 21.0), [255, 255, 255, 255], "at {}x", scale);
+    }
+}
+
+#[test]
+fn display_list_scale() {
+    let mut list = DisplayList::new(120, 90).expect("create display list");
+    list.set_scale_factor(2.0);
+    assert_eq!(list.bounds(), Rect::wh(60.0, 45.0));
+    list.start_paint();
+    list.clear(Color::white());
+    list.set_color(Color::black());
+    list.fill_rect(Rect::xywh(10.0, 10.0, 20.0, 20.0));
+    list.end_paint();
+    let (_, expected) = draw_at(2.0);
+    let px = list.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(21, 21), expected.pixel(21, 21));
+    assert_eq!(px.pixel(59, 59), expected.pixel(59, 59));
+    assert_eq!(px.pixel(61, 40), expected.pixel(61, 40));
+}
+
+struct Scaled {
+    scales: Vec<f64>,
+    bounds: Rect,
+    cursor: Point
+}
+
+impl App for Scaled {
+    fn init(rx: &mut RenderContext) -> Self {
+        Scaled { scales: Vec::new(), bounds: rx.bounds(), cursor: Point::xy(0.0, 0.0) }
+    }
+
+    fn paint(&mut self, rx: &mut RenderContext) {
+        self.bounds = rx.bounds();
+    }
+
+    fn event(&mut self, e: Event, _: &mut ControlFlowOpts, _: &mut bool) {
+        match e {
+            Event::ScaleFactorChanged { scale_factor, .. } => self.scales.push(scale_factor),
+            Event::CursorMoved { position, .. } => self.cursor = Point::xy(position.x as f32, position.y as f32),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn scale_factor_changed() {
+    let mut d = Driver::<Scaled>::new(200, 100).expect("create driver");
+    let frames = d.frame_count();
+    d.scale_factor_changed(2.0);
+    assert_eq!(d.app.scales, vec![2.0]);
+    // the window keeps its size in points, so it grows in pixels, and is repainted
+    assert_eq!(d.rx.scale_factor(), 2.0);
+    assert_eq!(d.rx.bounds(), Rect::wh(200.0, 100.0));
+    assert_eq!(d.rx.read_pixels().expect("read pixels").width, 400);
+    assert_eq!(d.app.bounds, Rect::wh(200.0, 100.0));
+    assert!(d.frame_count() > frames);
+
+    // the cursor is reported in pixels, and the App sees it in points
+    d.cursor_moved(Point::xy(100.0, 50.0));
+    assert_eq!((d.app.cursor.x, d.app.cursor.y), (50.0, 25.0));
+    d.scale_factor_changed(1.5);
+    assert_eq!(d.rx.bounds(), Rect::wh(200.0, 100.0));
+    assert_eq!(d.rx.read_pixels().expect("read pixels").width, 300);
+}
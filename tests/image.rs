@@ -0,0 +1,47 @@
+use runic::*;
+
+#[test]
+fn image() {
+    let mut checker = PixelBuffer::new(2, 2);
+    checker.data.copy_from_slice(&[
+        255, 0, 0, 255,   0, 255, 0, 255,
+        0, 0, 255, 255,   0, 0, 0, 0,
+    ]);
+    let path = std::env::temp_dir().join(format!("runic-image-test-{}.png", std::process::id()));
+    checker.write_png(&path).expect("write png");
+
+    let mut rx = RenderContext::new_offscreen(160, 40).expect("create offscreen render context");
+    let img = rx.new_image(&checker).expect("create image");
+    let loaded = rx.load_image(&path).expect("load image");
+    std::fs::remove_file(&path).expect("remove png");
+    let b = loaded.bounds();
+    assert_eq!((b.w, b.h), (2.0, 2.0));
+
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::black());
+    rx.draw_image(&img, img.bounds(), Rect::xywh(0.0, 0.0, 40.0, 40.0), 1.0, ImageFilter::Nearest);
+    // just the green pixel, then the red one at half opacity
+    rx.draw_image(&loaded, Rect::xywh(1.0, 0.0, 1.0, 1.0), Rect::xywh(50.0, 0.0, 20.0, 20.0), 1.0, ImageFilter::Nearest);
+    rx.draw_image(&img, Rect::xywh(0.0, 0.0, 1.0, 1.0), Rect::xywh(50.0, 20.0, 20.0, 20.0), 0.5, ImageFilter::Linear);
+    // the top row smoothly scaled from red to green
+    rx.draw_image(&img, Rect::xywh(0.0, 0.0, 2.0, 1.0), Rect::xywh(80.0, 0.0, 80.0, 40.0), 1.0, ImageFilter::Linear);
+    // drawing images leaves the current brush alone
+    rx.fill_rect(Rect::xywh(72.0, 0.0, 4.0, 4.0));
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(10, 10), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(30, 10), [0, 255, 0, 255]);
+    assert_eq!(px.pixel(10, 30), [0, 0, 255, 255]);
+    assert_eq!(px.pixel(30, 30), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(60, 10), [0, 255, 0, 255]);
+    let faded = px.pixel(60, 30);
+    assert_eq!(faded[0], 255);
+    assert!((faded[1] as i32 - 128).abs() <= 1 && (faded[2] as i32 - 128).abs() <= 1);
+    assert_eq!(px.pixel(82, 20), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(158, 20), [0, 255, 0, 255]);
+    let mid = px.pixel(120, 20);
+    assert!(mid[0] > 64 && mid[0] < 192 && mid[1] > 64 && mid[1] < 192);
+    assert_eq!(px.pixel(73, 1), [0, 0, 0, 255]);
+}
@@ -0,0 +1,43 @@
+use runic::*;
+
+#[test]
+fn layers() {
+    let mut rx = RenderContext::new_offscreen(100, 40).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::rgb(0.5, 0.5, 0.5));
+
+    // two overlapping squares faded together don't show through each other
+    rx.push_layer(0.5, BlendMode::Normal);
+    rx.set_color(Color::black());
+    rx.fill_rect(Rect::xywh(0.0, 0.0, 20.0, 20.0));
+    rx.fill_rect(Rect::xywh(10.0, 0.0, 20.0, 20.0));
+    rx.pop_layer();
+
+    rx.push_layer(1.0, BlendMode::Multiply);
+    rx.set_color(Color::rgb(1.0, 0.5, 0.0));
+    rx.fill_rect(Rect::xywh(40.0, 0.0, 20.0, 20.0));
+    rx.pop_layer();
+
+    rx.push_layer(1.0, BlendMode::Screen);
+    rx.set_color(Color::rgb(1.0, 0.5, 0.0));
+    rx.fill_rect(Rect::xywh(70.0, 0.0, 20.0, 20.0));
+    // the clip is popped along with the layer
+    rx.push_clip_rect(Rect::xywh(70.0, 0.0, 5.0, 5.0));
+    rx.pop_layer();
+    rx.fill_rect(Rect::xywh(0.0, 30.0, 10.0, 10.0));
+
+    // clearing replaces what's there, even with a transparent color
+    rx.push_clip_rect(Rect::xywh(90.0, 30.0, 10.0, 10.0));
+    rx.clear(Color::rgba(0.0, 0.0, 0.0, 0.0));
+    rx.pop_clip();
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    snapshot::assert_pixel(&px, 15, 10, [64, 64, 64, 255], 2);
+    snapshot::assert_pixel(&px, 5, 10, [64, 64, 64, 255], 2);
+    snapshot::assert_pixel(&px, 50, 10, [128, 64, 0, 255], 2);
+    snapshot::assert_pixel(&px, 80, 10, [255, 191, 128, 255], 2);
+    assert_eq!(px.pixel(5, 35), [255, 128, 0, 255]);
+    assert_eq!(px.pixel(95, 35), [0, 0, 0, 0]);
+    snapshot::assert_pixel(&px, 80, 35, [128, 128, 128, 255], 2);
+}
@@ -0,0 +1,41 @@
+use runic::*;
+
+#[test]
+fn offscreen() {
+    let mut rx = RenderContext::new_offscreen(320, 240).expect("create offscreen render context");
+    let b = rx.bounds();
+    assert_eq!((b.w, b.h), (320.0, 240.0));
+
+    let font = rx.new_font("Arial", 24.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let layout = rx.new_text_layout("Hello, offscreen!", &font, 320.0, 240.0).expect("create text layout");
+    let lb = layout.bounds();
+    assert!(lb.w > 0.0 && lb.h > 0.0);
+    assert!(layout.char_bounds(4).x > 0.0);
+    assert!(layout.hit_test(Point::xy(1.0, 1.0)).is_some());
+
+    rx.start_paint();
+    rx.clear(Color::rgb(1.0, 0.4, 0.05));
+    rx.set_color(Color::rgb(0.4, 0.05, 1.0));
+    rx.fill_rect(Rect::xywh(8.0, 8.0, 100.0, 100.0));
+    rx.stroke_rect(Rect::xywh(116.0, 8.0, 100.0, 100.0), 4.0);
+    rx.draw_line(Point::xy(16.0, 16.0), Point::xy(94.0, 94.0), 3.0);
+    rx.draw_text(Rect::xywh(8.0, 120.0, 300.0, 40.0), "Hello, draw_text!", &font);
+    rx.draw_text_layout(Point::xy(8.0, 160.0), &layout);
+    rx.end_paint();
+
+    rx.resize(64, 32).expect("resize render context");
+    let b = rx.bounds();
+    assert_eq!((b.w, b.h), (64.0, 32.0));
+    rx.start_paint();
+    rx.clear(Color::black());
+    rx.end_paint();
+
+    // cairo can't make a surface this large, so the old one is kept
+    assert!(rx.resize(100_000, 100_000).is_err());
+    let b = rx.bounds();
+    assert_eq!((b.w, b.h), (64.0, 32.0));
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.end_paint();
+    assert_eq!(rx.read_pixels().expect("read pixels").pixel(0, 0), [255, 255, 255, 255]);
+}
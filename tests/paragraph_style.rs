@@ -0,0 +1,95 @@
+use runic::*;
+
+const TEXT: &str = "Paragraphs can be aligned to either side of the layout, centered, or justified";
+
+#[test]
+fn paragraph_style() {
+    let rx = RenderContext::new_offscreen(300, 300).expect("create offscreen render context");
+    let font = rx.new_font("Arial", 16.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let txl = rx.new_text_layout(TEXT, &font, 200.0, 300.0).expect("create text layout");
+    let plain = txl.bounds();
+    let left = txl.char_bounds(0);
+    assert_eq!(left.x, 0.0);
+
+    // the first line is moved over to the other side, or halfway
+    txl.set_paragraph_style(&ParagraphStyle { alignment: TextAlignment::Right, ..Default::default() });
+    let right = txl.char_bounds(0);
+    assert!(right.x > 0.0 && right.x < 200.0);
+    txl.set_paragraph_style(&ParagraphStyle { alignment: TextAlignment::Center, ..Default::default() });
+    assert!((txl.char_bounds(0).x - right.x / 2.0).abs() <= 1.0);
+
+    // justified lines reach the edge, except the last
+    txl.set_paragraph_style(&ParagraphStyle { alignment: TextAlignment::Justify, ..Default::default() });
+    assert_eq!(txl.char_bounds(0).x, 0.0);
+    let (end, _) = txl.hit_test(Point::xy(199.0, left.h / 2.0)).expect("hit the end of the first line");
+    let end = txl.char_bounds(end);
+    assert!(end.x + end.w >= 198.0, "first line ends at {:?}", end);
+    let last = txl.char_bounds(TEXT.len() - 1);
+    assert!(last.x + last.w < 190.0, "last line ends at {:?}", last);
+
+    // lines are spaced out, and the first is indented
+    txl.set_paragraph_style(&ParagraphStyle { line_spacing: 2.0, first_line_indent: 20.0, ..Default::default() });
+    assert!(txl.bounds().h > plain.h * 1.5, "spaced out to {:?}", txl.bounds());
+    assert_eq!(txl.char_bounds(0).x, 20.0);
+
+    txl.set_paragraph_style(&ParagraphStyle::default());
+    assert_eq!(txl.bounds(), plain);
+    assert_eq!(txl.char_bounds(0), left);
+
+    let tabbed = rx.new_text_layout("a\tb\tc", &font, 300.0, 300.0).expect("create text layout");
+    tabbed.set_paragraph_style(&ParagraphStyle { tab_stops: vec![50.0, 120.0], ..Default::default() });
+    assert_eq!(tabbed.char_bounds(2).x, 50.0);
+    assert_eq!(tabbed.char_bounds(4).x, 120.0);
+}
+
+#[test]
+fn paragraph_spacing() {
+    let mut rx = RenderContext::new_offscreen(300, 300).expect("create offscreen render context");
+    let font = rx.new_font("Arial", 16.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let text = "First paragraph\nSecond paragraph, which wraps\nThird";
+    let txl = rx.new_text_layout(text, &font, 200.0, 300.0).expect("create text layout");
+    let plain = txl.bounds();
+    let lines = txl.lines();
+    assert!(lines.len() > 3);
+    let third = text.rfind('T').expect("third paragraph");
+
+    // every paragraph after the first is moved down, but lines within a paragraph aren't
+    txl.set_paragraph_style(&ParagraphStyle { paragraph_spacing: 10.0, ..Default::default() });
+    assert_eq!(txl.bounds().h, plain.h + 20.0);
+    let spaced = txl.lines();
+    for (s, l) in spaced.iter().zip(lines.iter()) {
+        let above = text[..l.range.start].matches('\n').count() as f32;
+        assert_eq!(s.baseline - l.baseline, above * 10.0, "{:?} moved to {:?}", l, s);
+        assert_eq!(s.bounds.y - l.bounds.y, above * 10.0);
+    }
+    let last = spaced.last().expect("last line").bounds;
+    let before = spaced[spaced.len() - 2].bounds;
+    assert_eq!(txl.char_bounds(third).y, last.y);
+    let (index, _) = txl.hit_test(Point::xy(1.0, last.y + 1.0)).expect("hit the third paragraph");
+    assert_eq!(index, third);
+
+    // the text is drawn where the lines are
+    let draw = |rx: &mut RenderContext| {
+        rx.start_paint();
+        rx.clear(Color::white());
+        rx.set_color(Color::black());
+        rx.draw_text_layout(Point::xy(0.0, 0.0), &txl);
+        rx.end_paint();
+        rx.read_pixels().expect("read pixels")
+    };
+    let px = draw(&mut rx);
+    let inked = |top: f32, bottom: f32| (top as u32..bottom as u32).any(|y| (0..200).any(|x| px.pixel(x, y)[0] < 128));
+    assert!(inked(last.y, last.y + last.h));
+    assert!(!inked(before.y + before.h, last.y));
+
+    // ellipsized text still fits the height once it is spaced out
+    let short = rx.new_text_layout(text, &font, 200.0, lines[2].bounds.y + lines[2].bounds.h).expect("create text layout");
+    short.set_ellipsize(EllipsizeMode::End);
+    assert_eq!(short.lines().len(), 3);
+    short.set_paragraph_style(&ParagraphStyle { paragraph_spacing: 10.0, ..Default::default() });
+    assert_eq!(short.lines().len(), 2);
+
+    txl.set_paragraph_style(&ParagraphStyle::default());
+    assert_eq!(txl.bounds(), plain);
+    assert!(!snapshot::compare(&draw(&mut rx), &px, 0).matches());
+}
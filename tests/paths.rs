@@ -0,0 +1,42 @@
+use runic::*;
+use std::f32::consts::PI;
+
+#[test]
+fn paths() {
+    let mut rx = RenderContext::new_offscreen(128, 64).expect("create offscreen render context");
+
+    let mut circle = Path::new();
+    circle.arc_to(Point::xy(32.0, 32.0), 24.0, 0.0, PI * 2.0).close();
+
+    let mut curve = Path::new();
+    curve.move_to(Point::xy(64.0, 60.0))
+        .cubic_to(Point::xy(64.0, 0.0), Point::xy(124.0, 0.0), Point::xy(124.0, 60.0))
+        .quad_to(Point::xy(94.0, 30.0), Point::xy(64.0, 60.0))
+        .close();
+
+    let mut wedge = Path::new();
+    wedge.move_to(Point::xy(32.0, 32.0)).arc_to(Point::xy(32.0, 32.0), 30.0, PI / 2.0, 0.0).close();
+
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::rgb(1.0, 0.0, 0.0));
+    rx.fill_path(&circle);
+    rx.set_color(Color::rgb(0.0, 0.0, 1.0));
+    rx.fill_path(&curve);
+    rx.set_color(Color::black());
+    rx.stroke_path(&wedge, 2.0);
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    // inside and outside the circle
+    assert_eq!(px.pixel(26, 38), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(20, 20), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(4, 4), [255, 255, 255, 255]);
+    // inside the area between the curves, and outside it below the quadratic curve
+    assert_eq!(px.pixel(94, 20), [0, 0, 255, 255]);
+    assert_eq!(px.pixel(94, 55), [255, 255, 255, 255]);
+    // the wedge's arc is swept counter-clockwise from the bottom to the right, so it passes
+    // through the bottom right and not the top right
+    assert_ne!(px.pixel(53, 53), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(53, 10), [255, 255, 255, 255]);
+}
@@ -0,0 +1,23 @@
+use runic::*;
+
+#[test]
+fn pixels() {
+    let mut rx = RenderContext::new_offscreen(64, 32).expect("create offscreen render context");
+    rx.start_paint();
+    rx.set_color(Color::rgb(1.0, 0.0, 0.0));
+    rx.fill_rect(Rect::xywh(0.0, 0.0, 16.0, 16.0));
+    rx.set_color(Color::rgba(0.0, 0.0, 1.0, 0.5));
+    rx.fill_rect(Rect::xywh(32.0, 0.0, 16.0, 16.0));
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!((px.width, px.height), (64, 32));
+    assert!(px.stride >= 64 * 4);
+    assert_eq!(px.data.len(), px.stride * 32);
+
+    assert_eq!(px.pixel(8, 8), [255, 0, 0, 255]);
+    let blue = px.pixel(40, 8);
+    assert_eq!(&blue[0..3], &[0, 0, 255]);
+    assert!((blue[3] as i32 - 128).abs() <= 1);
+    assert_eq!(px.pixel(20, 24), [0, 0, 0, 0]);
+}
@@ -0,0 +1,49 @@
+use runic::*;
+use runic::display_list::{Command, DisplayList};
+
+fn draw<R: RenderContextExt>(rx: &mut R) -> Result<PixelBuffer, Box<dyn std::error::Error>> {
+    let mut target = rx.new_render_target(20, 20)?;
+    target.start_paint();
+    target.clear(Color::rgba(0.0, 0.0, 0.0, 0.0));
+    target.set_color(Color::rgb(1.0, 0.0, 0.0));
+    target.fill_rect(Rect::xywh(0.0, 0.0, 10.0, 20.0));
+    target.end_paint();
+
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::rgb(0.0, 0.0, 1.0));
+    rx.draw_render_target(&target, &Affine::translate(Point::xy(10.0, 10.0)), 1.0);
+    rx.translate(Point::xy(50.0, 0.0));
+    rx.draw_render_target(&target, &Affine::scale(2.0, 2.0), 0.5);
+    // drawing a target leaves the brush and transform alone
+    rx.fill_rect(Rect::xywh(40.0, 50.0, 4.0, 4.0));
+    rx.end_paint();
+    rx.read_pixels()
+}
+
+#[test]
+fn render_target() {
+    let mut rx = RenderContext::new_offscreen(100, 60).expect("create offscreen render context");
+    let px = draw(&mut rx).expect("draw render target");
+    snapshot::assert_pixel(&px, 15, 15, [255, 0, 0, 255], 2);
+    snapshot::assert_pixel(&px, 25, 15, [255, 255, 255, 255], 2);
+    snapshot::assert_pixel(&px, 65, 30, [255, 128, 128, 255], 2);
+    snapshot::assert_pixel(&px, 75, 30, [255, 255, 255, 255], 2);
+    snapshot::assert_pixel(&px, 92, 52, [0, 0, 255, 255], 2);
+
+    let mut list = DisplayList::new(100, 60).expect("create display list");
+    let recorded = draw(&mut list).expect("record render target");
+    // the target is replayed as vectors, so the edge of the scaled copy is sharp instead of
+    // stretched from its pixels, and everything else is the same
+    for y in 0..px.height {
+        for x in 0..px.width {
+            if x != 69 && x != 70 {
+                snapshot::assert_pixel(&recorded, x, y, px.pixel(x, y), 2);
+            }
+        }
+    }
+    snapshot::assert_pixel(&recorded, 70, 30, [255, 255, 255, 255], 2);
+    // the target's drawing is recorded as commands instead of an image
+    assert!(list.commands().contains(&Command::FillRect(Rect::xywh(0.0, 0.0, 10.0, 20.0))));
+    assert!(!list.commands().iter().any(|c| matches!(c, Command::DrawImage { .. })));
+}
@@ -0,0 +1,35 @@
+use runic::*;
+
+#[test]
+fn rounded() {
+    let mut rx = RenderContext::new_offscreen(160, 80).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::rgb(1.0, 0.0, 0.0));
+    rx.fill_rounded_rect(Rect::xywh(0.0, 0.0, 80.0, 80.0),
+        CornerRadii { top_left: 30.0, top_right: 0.0, bottom_right: 0.0, bottom_left: 500.0 });
+    rx.set_color(Color::rgb(0.0, 0.0, 1.0));
+    rx.fill_ellipse(Point::xy(120.0, 40.0), 36.0, 20.0);
+    rx.set_color(Color::black());
+    rx.stroke_rounded_rect(Rect::xywh(84.0, 2.0, 72.0, 76.0), 8.0.into(), 2.0);
+    rx.stroke_ellipse(Point::xy(120.0, 40.0), 10.0, 10.0, 2.0);
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    // the top left corner is cut off, the top right and bottom right are square
+    assert_eq!(px.pixel(2, 2), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(78, 1), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(78, 78), [255, 0, 0, 255]);
+    // the bottom left radius is limited to half the size of the rectangle
+    assert_eq!(px.pixel(4, 76), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(40, 40), [255, 0, 0, 255]);
+
+    // inside the ellipse but outside the circle, and outside the ellipse
+    assert_eq!(px.pixel(145, 40), [0, 0, 255, 255]);
+    assert_eq!(px.pixel(120, 40), [0, 0, 255, 255]);
+    assert_eq!(px.pixel(120, 65), [255, 255, 255, 255]);
+    // on the circle and the rounded rectangle outlines, but not in the rounded corner
+    assert_eq!(px.pixel(110, 40), [0, 0, 0, 255]);
+    assert_eq!(px.pixel(120, 2), [0, 0, 0, 255]);
+    assert_eq!(px.pixel(84, 2), [255, 255, 255, 255]);
+}
@@ -0,0 +1,32 @@
+use runic::*;
+
+#[test]
+fn shadow() {
+    let mut rx = RenderContext::new_offscreen(200, 100).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::rgb(0.0, 0.0, 1.0));
+    let card = Shape::RoundedRect(Rect::xywh(20.0, 20.0, 60.0, 40.0), 6.0.into());
+    rx.draw_shadow(&card, Point::xy(4.0, 8.0), 10.0, Color::black());
+    rx.fill_rounded_rect(Rect::xywh(20.0, 20.0, 60.0, 40.0), 6.0.into());
+    // without any blur the shadow has hard edges
+    rx.draw_shadow(&Rect::xywh(120.0, 20.0, 40.0, 40.0).into(), Point::xy(10.0, 10.0), 0.0, Color::rgb(1.0, 0.0, 0.0));
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    let grey = |x, y| px.pixel(x, y)[0] as i32;
+    // the card covers its shadow, and the brush is left alone
+    assert_eq!(px.pixel(50, 40), [0, 0, 255, 255]);
+    // well inside the offset shadow it is almost solid, and it fades out past the edge
+    assert!(grey(60, 61) < 48);
+    let edge = grey(60, 68);
+    assert!(edge > 96 && edge < 160, "edge of shadow is {}", edge);
+    assert!(grey(60, 72) > edge && grey(60, 72) < 255);
+    assert_eq!(grey(60, 95), 255);
+    assert_eq!(grey(4, 40), 255);
+    assert!(grey(90, 40) < grey(95, 40));
+
+    assert_eq!(px.pixel(165, 65), [255, 0, 0, 255]);
+    assert_eq!(px.pixel(171, 65), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(169, 69), [255, 0, 0, 255]);
+}
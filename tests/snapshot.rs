@@ -0,0 +1,48 @@
+use runic::*;
+
+struct TestApp {
+}
+
+impl App for TestApp {
+    fn init(_: &mut RenderContext) -> Self { TestApp {} }
+
+    fn paint(&mut self, rx: &mut RenderContext) {
+        rx.clear(Color::rgb(1.0, 0.4, 0.05));
+        rx.set_color(Color::rgb(0.4, 0.05, 1.0));
+        rx.stroke_rect(Rect::xywh(8.0, 8.0, 100.0, 100.0), 8.0);
+        rx.fill_rect(Rect::xywh(116.0, 8.0, 100.0, 100.0));
+        rx.set_color(Color::rgb(0.0, 0.0, 0.6));
+        rx.draw_line(Point::xy(16.0, 16.0), Point::xy(94.0, 94.0), 3.0);
+    }
+
+    fn event(&mut self, e: Event, event_loop_flow: &mut ControlFlowOpts, _: &mut bool) {
+        if let Event::CloseRequested = e { *event_loop_flow = ControlFlowOpts::Exit; }
+    }
+}
+
+#[test]
+fn snapshot() {
+    let reference = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/shapes.png");
+    snapshot::assert_app_snapshot::<TestApp, _>(reference, 224, 116, 2);
+
+    let mut changed = snapshot::render_app::<TestApp>(224, 116).expect("render app");
+    changed.data[0..4].copy_from_slice(&[0, 255, 0, 255]);
+    let cmp = snapshot::compare(&changed, &PixelBuffer::load_png(reference).expect("load reference"), 2);
+    assert_eq!(cmp.differing_pixels, 1);
+    assert_eq!(cmp.diff_image.pixel(0, 0), [255, 0, 0, 255]);
+}
+
+#[test]
+fn missing_snapshot() {
+    if std::env::var_os("RUNIC_UPDATE_SNAPSHOTS").is_some() {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("runic-snapshot-{}", std::process::id()));
+    let reference = dir.join("missing.png");
+    let actual = snapshot::render_app::<TestApp>(224, 116).expect("render app");
+    // a missing reference fails instead of being written and passing
+    assert!(std::panic::catch_unwind(|| snapshot::assert_snapshot(&actual, &reference, 2)).is_err());
+    assert!(!reference.exists());
+    assert!(dir.join("missing.actual.png").exists());
+    std::fs::remove_dir_all(&dir).expect("remove snapshot directory");
+}
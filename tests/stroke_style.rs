@@ -0,0 +1,33 @@
+use runic::*;
+
+#[test]
+fn stroke_style() {
+    let mut rx = RenderContext::new_offscreen(200, 60).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::black());
+    // dashes are 8 pixels long with 4 pixel gaps at this width
+    rx.set_stroke_style(&StrokeStyle::dashed(&[2.0, 1.0]));
+    rx.draw_line(Point::xy(0.0, 10.0), Point::xy(200.0, 10.0), 4.0);
+    rx.set_stroke_style(&StrokeStyle { dash_offset: 1.0, ..StrokeStyle::dashed(&[2.0, 1.0]) });
+    rx.draw_line(Point::xy(0.0, 50.0), Point::xy(200.0, 50.0), 4.0);
+    rx.set_stroke_style(&StrokeStyle { cap: LineCap::Round, ..StrokeStyle::default() });
+    rx.draw_line(Point::xy(20.0, 30.0), Point::xy(60.0, 30.0), 10.0);
+    rx.set_stroke_style(&StrokeStyle::default());
+    rx.draw_line(Point::xy(100.0, 30.0), Point::xy(140.0, 30.0), 10.0);
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(4, 10), [0, 0, 0, 255]);
+    assert_eq!(px.pixel(10, 10), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(14, 10), [0, 0, 0, 255]);
+    // the offset pattern starts halfway through the first dash
+    assert_eq!(px.pixel(2, 50), [0, 0, 0, 255]);
+    assert_eq!(px.pixel(6, 50), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(10, 50), [0, 0, 0, 255]);
+    // round caps extend past the end points, butt caps don't
+    assert_eq!(px.pixel(16, 30), [0, 0, 0, 255]);
+    assert_eq!(px.pixel(16, 24), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(97, 30), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(120, 30), [0, 0, 0, 255]);
+}
@@ -0,0 +1,50 @@
+use runic::*;
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog and keeps running";
+
+#[test]
+fn text_fit() {
+    let rx = RenderContext::new_offscreen(200, 200).expect("create offscreen render context");
+    let font = rx.new_font("Arial", 16.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let line = rx.new_text_layout("Line", &font, 120.0, 200.0).expect("create text layout").bounds().h;
+
+    // text wraps to the width it was created with
+    let txl = rx.new_text_layout(TEXT, &font, 120.0, 200.0).expect("create text layout");
+    let wrapped = txl.bounds();
+    assert!(wrapped.w <= 120.0, "wrapped to {:?}", wrapped);
+    assert!(wrapped.h > line * 3.0, "wrapped to {:?}", wrapped);
+
+    txl.set_wrap(WrapMode::None);
+    assert_eq!(txl.bounds().h, line);
+    assert!(txl.bounds().w > 300.0);
+
+    // long words only break between characters if asked to
+    let word = rx.new_text_layout("Supercalifragilisticexpialidocious", &font, 120.0, 200.0).expect("create text layout");
+    assert!(word.bounds().w > 120.0);
+    word.set_wrap(WrapMode::WordChar);
+    assert!(word.bounds().w <= 120.0 && word.bounds().h > line);
+    word.set_wrap(WrapMode::Char);
+    assert!(word.bounds().w <= 120.0);
+
+    // unwrapped text is ellipsized to one line, and keeps the end visible when ellipsized in the middle
+    let path = "/home/user/projects/runic/src/cairo_context.rs";
+    let tab = rx.new_text_layout(path, &font, 120.0, 200.0).expect("create text layout");
+    tab.set_wrap(WrapMode::None);
+    tab.set_ellipsize(EllipsizeMode::Middle);
+    assert!(tab.bounds().w <= 120.0 && tab.bounds().h == line, "ellipsized to {:?}", tab.bounds());
+    let last = path.len() - 1;
+    let (index, _) = tab.hit_test(Point::xy(tab.bounds().w - 1.0, line / 2.0)).expect("hit the end");
+    assert_eq!(index, last);
+
+    // ellipsized text that wraps fits the height, or the number of lines
+    let txl = rx.new_text_layout(TEXT, &font, 120.0, line * 2.5).expect("create text layout");
+    txl.set_ellipsize(EllipsizeMode::End);
+    assert!(txl.bounds().h <= line * 2.5 && txl.bounds().h > line, "ellipsized to {:?}", txl.bounds());
+    let txl = rx.new_text_layout(TEXT, &font, 120.0, 200.0).expect("create text layout");
+    txl.set_max_lines(Some(3));
+    assert_eq!(txl.bounds(), wrapped);
+    txl.set_ellipsize(EllipsizeMode::End);
+    assert!((txl.bounds().h - line * 3.0).abs() < 1.0, "limited to {:?}", txl.bounds());
+    txl.set_max_lines(None);
+    assert_eq!(txl.bounds(), wrapped);
+}
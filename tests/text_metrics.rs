@@ -0,0 +1,46 @@
+use runic::*;
+
+const TEXT: &str = "The first paragraph is long enough to wrap onto another line\nThe second isn't";
+
+#[test]
+fn text_metrics() {
+    let rx = RenderContext::new_offscreen(200, 200).expect("create offscreen render context");
+    let font = rx.new_font("Arial", 16.0, FontWeight::Regular, FontStyle::Normal).expect("load font");
+    let m = font.metrics();
+    assert!(m.ascent > 10.0 && m.ascent < 24.0, "{:?}", m);
+    assert!(m.descent > 2.0 && m.descent < m.ascent, "{:?}", m);
+    assert!(m.x_height > 5.0 && m.x_height < m.ascent * 0.75, "{:?}", m);
+
+    let txl = rx.new_text_layout(TEXT, &font, 200.0, 200.0).expect("create text layout");
+    let lines = txl.lines();
+    assert!(lines.len() >= 3, "{:?}", lines);
+
+    // the lines cover all of the text but the line break between the paragraphs, in order
+    let newline = TEXT.find('\n').unwrap();
+    assert_eq!(lines[0].range.start, 0);
+    assert_eq!(lines[lines.len() - 2].range.end, newline);
+    assert_eq!(lines[lines.len() - 1].range, newline + 1..TEXT.len());
+    for pair in lines.windows(2) {
+        assert!(pair[0].range.end <= pair[1].range.start);
+        assert_eq!(pair[0].bounds.y + pair[0].bounds.h, pair[1].bounds.y);
+    }
+    let text: String = lines.iter().map(|l| &TEXT[l.range.clone()]).collect();
+    assert_eq!(text.replace(' ', ""), TEXT.replace([' ', '\n'], ""));
+
+    // baselines are the font's ascent below the top of each line, and the lines fill the layout
+    for line in &lines {
+        assert!((line.baseline - line.bounds.y - m.ascent).abs() <= 1.0, "{:?}", line);
+        assert!(line.bounds.w > 0.0 && line.bounds.w <= 200.0);
+    }
+    let last = lines.last().unwrap().bounds;
+    assert_eq!(last.y + last.h, txl.bounds().h);
+
+    // the first character sits on the first baseline
+    let c = txl.char_bounds(0);
+    assert!((c.y + m.ascent - lines[0].baseline).abs() <= 1.0);
+
+    let centered = rx.new_text_layout("Short", &font, 200.0, 200.0).expect("create text layout");
+    centered.set_paragraph_style(&ParagraphStyle { alignment: TextAlignment::Center, ..Default::default() });
+    let line = &centered.lines()[0];
+    assert!((line.bounds.x + line.bounds.w / 2.0 - 100.0).abs() <= 1.0, "{:?}", line);
+}
@@ -0,0 +1,100 @@
+use runic::*;
+use runic::driver::Driver;
+use std::time::{Duration, Instant};
+
+const BLINK: Duration = Duration::from_millis(500);
+
+struct Blink {
+    caret: bool,
+    next_blink: Option<Instant>,
+    blinked_at: Vec<Instant>,
+    x: f32,
+    target: f32,
+    ticks: Vec<Duration>
+}
+
+impl App for Blink {
+    fn init(_: &mut RenderContext) -> Self {
+        Blink { caret: true, next_blink: None, blinked_at: Vec::new(), x: 0.0, target: 0.0, ticks: Vec::new() }
+    }
+
+    fn paint(&mut self, rx: &mut RenderContext) {
+        rx.clear(Color::white());
+        rx.set_color(Color::black());
+        if self.caret {
+            rx.fill_rect(Rect::xywh(2.0, 2.0, 2.0, 16.0));
+        }
+        rx.fill_rect(Rect::xywh(self.x, 30.0, 10.0, 10.0));
+    }
+
+    fn event(&mut self, e: Event, _: &mut ControlFlowOpts, should_redraw: &mut bool) {
+        if let Event::ReceivedCharacter('m') = e {
+            self.target = 40.0;
+            *should_redraw = true;
+        }
+    }
+
+    fn next_timer(&self) -> Option<Instant> { self.next_blink }
+
+    fn timer(&mut self, now: Instant, damage: &mut Damage) {
+        self.caret = !self.caret;
+        self.blinked_at.push(now);
+        self.next_blink = self.next_blink.map(|t| t + BLINK);
+        damage.add(Rect::xywh(2.0, 2.0, 2.0, 16.0));
+    }
+
+    fn is_animating(&self) -> bool { self.x < self.target }
+
+    fn tick(&mut self, dt: Duration) {
+        self.ticks.push(dt);
+        // 100 pixels a second
+        self.x = (self.x + dt.as_secs_f32() * 100.0).min(self.target);
+    }
+}
+
+#[test]
+fn timers() {
+    let mut d = Driver::<Blink>::new(60, 40).expect("create driver");
+    let start = d.now();
+    d.advance(Duration::from_secs(1));
+    assert!(d.app.blinked_at.is_empty());
+    assert_eq!(d.frame_count(), 1);
+
+    d.app.next_blink = Some(d.now() + BLINK);
+    d.advance(Duration::from_millis(400));
+    assert!(d.app.blinked_at.is_empty());
+    d.advance(Duration::from_millis(700));
+    // timers fire at the time they were due, even when time jumps past several of them
+    assert_eq!(d.app.blinked_at, vec![start + Duration::from_millis(1500), start + Duration::from_millis(2000)]);
+    assert_eq!(d.now(), start + Duration::from_millis(2100));
+    assert!(d.app.caret);
+    // the event loop wakes up for each of them, and paints after each
+    assert_eq!(d.frame_count(), 3);
+    d.advance(BLINK);
+    assert!(!d.app.caret);
+    assert_eq!(d.frame().pixel(3, 10), [255, 255, 255, 255]);
+
+    d.app.next_blink = None;
+    let frames = d.frame_count();
+    d.received_character('m');
+    // the first frame of an animation is painted straight away, without a tick
+    assert!(d.app.ticks.is_empty());
+    assert_eq!(d.frame_count(), frames + 1);
+    // then it's ticked and painted about 60 times a second, however the clock is moved on
+    d.advance(Duration::from_millis(100));
+    assert_eq!(d.app.ticks.len(), 5);
+    assert_eq!(d.frame_count(), frames + 6);
+    for _ in 0..10 {
+        d.advance(Duration::from_millis(5));
+    }
+    assert_eq!(d.app.ticks.len(), 8);
+    assert!(d.app.ticks.iter().all(|dt| *dt == d.app.ticks[0]));
+    assert!((d.app.ticks[0].as_secs_f32() - 1.0 / 60.0).abs() < 0.0001);
+    // once the animation finishes it isn't ticked any more
+    d.advance(Duration::from_secs(1));
+    let ticks = d.app.ticks.len();
+    d.advance(Duration::from_secs(1));
+    assert_eq!(d.app.ticks.len(), ticks);
+    assert_eq!(d.app.x, 40.0);
+    assert_eq!(d.frame().pixel(45, 35), [0, 0, 0, 255]);
+}
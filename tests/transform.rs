@@ -0,0 +1,51 @@
+use runic::*;
+use std::f32::consts::PI;
+
+fn close(a: Point, b: Point) -> bool {
+    (a.x - b.x).abs() < 1e-4 && (a.y - b.y).abs() < 1e-4
+}
+
+#[test]
+fn affine() {
+    let m = Affine::scale(2.0, 3.0).multiply(&Affine::rotate(PI / 2.0)).multiply(&Affine::translate(Point::xy(10.0, 0.0)));
+    // scaled to (2, 3), rotated clockwise to (-3, 2), then moved
+    assert!(close(m.transform_point(Point::xy(1.0, 1.0)), Point::xy(7.0, 2.0)));
+    let inv = m.invert().expect("invertible");
+    assert!(close(inv.transform_point(Point::xy(7.0, 2.0)), Point::xy(1.0, 1.0)));
+    assert!(close(Affine::skew(PI / 4.0, 0.0).transform_point(Point::xy(0.0, 2.0)), Point::xy(2.0, 2.0)));
+    assert_eq!(Affine::scale(0.0, 1.0).invert(), None);
+    assert_eq!(Affine::default(), Affine::identity());
+}
+
+#[test]
+fn transform() {
+    let mut rx = RenderContext::new_offscreen(100, 100).expect("create offscreen render context");
+    rx.start_paint();
+    rx.clear(Color::white());
+    rx.set_color(Color::black());
+    rx.translate(Point::xy(50.0, 50.0));
+    rx.save();
+    rx.transform(&Affine::scale(4.0, 4.0));
+    rx.transform(&Affine::rotate(PI / 2.0));
+    // a 5x2 rectangle rotated to stand upright and scaled up, below and to the left of the center
+    rx.fill_rect(Rect::xywh(0.0, 0.0, 5.0, 2.0));
+    let m = rx.current_transform();
+    assert!(close(m.transform_point(Point::xy(5.0, 0.0)), Point::xy(50.0, 70.0)));
+    assert!(close(m.invert().expect("invertible").transform_point(Point::xy(46.0, 60.0)), Point::xy(2.5, 1.0)));
+    rx.restore();
+    // translations accumulate, and restore returned to the saved translation
+    rx.translate(Point::xy(10.0, -40.0));
+    rx.fill_rect(Rect::xywh(0.0, 0.0, 10.0, 10.0));
+    rx.end_paint();
+
+    let px = rx.read_pixels().expect("read pixels");
+    assert_eq!(px.pixel(45, 65), [0, 0, 0, 255]);
+    assert_eq!(px.pixel(55, 65), [255, 255, 255, 255]);
+    assert_eq!(px.pixel(65, 15), [0, 0, 0, 255]);
+
+    rx.start_paint();
+    assert_eq!(rx.current_transform(), Affine::identity());
+    rx.set_transform(&Affine::translate(Point::xy(3.0, 4.0)));
+    assert_eq!(rx.current_transform(), Affine::translate(Point::xy(3.0, 4.0)));
+    rx.end_paint();
+}
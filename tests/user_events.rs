@@ -0,0 +1,66 @@
+use runic::*;
+use runic::driver::Driver;
+
+enum Message {
+    Diagnostics(Vec<String>),
+    Quit
+}
+
+struct Editor {
+    proxy: Option<EventProxy<Message>>,
+    diagnostics: Vec<String>
+}
+
+impl App for Editor {
+    fn init(_: &mut RenderContext) -> Self {
+        Editor { proxy: None, diagnostics: Vec::new() }
+    }
+
+    fn paint(&mut self, rx: &mut RenderContext) {
+        rx.clear(Color::white());
+        rx.set_color(Color::rgb(1.0, 0.0, 0.0));
+        for i in 0..self.diagnostics.len() {
+            rx.fill_rect(Rect::xywh(0.0, i as f32 * 10.0, 10.0, 8.0));
+        }
+    }
+
+    fn event(&mut self, _: Event, _: &mut ControlFlowOpts, _: &mut bool) {}
+}
+
+impl UserEventApp for Editor {
+    type UserEvent = Message;
+
+    fn event_proxy(&mut self, proxy: EventProxy<Message>) {
+        self.proxy = Some(proxy);
+    }
+
+    fn user_event(&mut self, e: Message, flow: &mut ControlFlowOpts, should_redraw: &mut bool) {
+        match e {
+            Message::Diagnostics(d) => {
+                self.diagnostics = d;
+                *should_redraw = true;
+            },
+            Message::Quit => *flow = ControlFlowOpts::Exit
+        }
+    }
+}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn user_events() {
+    // proxies can be sent to background threads
+    assert_send::<EventProxy<Message>>();
+
+    let mut d = Driver::<Editor>::new(20, 40).expect("create driver");
+    assert!(d.app.proxy.is_none());
+    d.user_event(Message::Diagnostics(vec!["unused variable".into(), "missing semicolon".into()]));
+    assert_eq!(d.frame_count(), 2);
+    assert_eq!(d.frame().pixel(5, 14), [255, 0, 0, 255]);
+    assert_eq!(d.frame().pixel(5, 24), [255, 255, 255, 255]);
+
+    d.user_event(Message::Quit);
+    assert!(d.exited());
+    d.user_event(Message::Diagnostics(Vec::new()));
+    assert_eq!(d.app.diagnostics.len(), 2);
+}
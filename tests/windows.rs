@@ -0,0 +1,81 @@
+use runic::*;
+use runic::driver::Driver;
+
+struct Editor {
+    palette: Option<WindowId>,
+    palette_clicks: usize
+}
+
+impl App for Editor {
+    fn init(_: &mut RenderContext) -> Self {
+        Editor { palette: None, palette_clicks: 0 }
+    }
+
+    fn paint(&mut self, rx: &mut RenderContext) {
+        rx.clear(Color::white());
+    }
+
+    fn paint_window(&mut self, window: WindowId, rx: &mut RenderContext) {
+        if Some(window) == self.palette {
+            rx.clear(Color::rgb(0.0, 0.0, self.palette_clicks as f32 / 2.0));
+        } else {
+            self.paint(rx);
+        }
+    }
+
+    fn event(&mut self, _: Event, _: &mut ControlFlowOpts, _: &mut bool) {}
+
+    fn window_event(&mut self, window: WindowId, e: Event, flow: &mut ControlFlowOpts, damage: &mut Damage, windows: &mut Windows) {
+        match e {
+            Event::ReceivedCharacter('p') if self.palette.is_none() => {
+                let opts = WindowOptions::new().with_inner_size(dpi::PhysicalSize::new(30, 20));
+                self.palette = Some(windows.open(opts).expect("open palette"));
+            },
+            Event::MouseInput { state: ElementState::Pressed, .. } if Some(window) == self.palette => {
+                self.palette_clicks += 1;
+                damage.add_all();
+            },
+            Event::CloseRequested if window == WindowId::MAIN => *flow = ControlFlowOpts::Exit,
+            Event::CloseRequested => {
+                windows.close(window);
+                self.palette = None;
+            },
+            _ => ()
+        }
+    }
+}
+
+#[test]
+fn windows() {
+    let mut d = Driver::<Editor>::new(60, 40).expect("create driver");
+    assert_eq!(d.window_ids(), vec![WindowId::MAIN]);
+    d.received_character('p');
+    let palette = d.app.palette.expect("palette opened");
+    assert_eq!(d.window_ids(), vec![WindowId::MAIN, palette]);
+    let b = d.window(palette).expect("palette window").bounds();
+    assert_eq!((b.w, b.h), (30.0, 20.0));
+
+    // events are routed to the window they're sent to, and only it is repainted
+    d.send_to(palette, Event::MouseInput {
+        device_id: unsafe { winit::event::DeviceId::dummy() },
+        state: ElementState::Pressed, button: MouseButton::Left,
+        #[allow(deprecated)]
+        modifiers: ModifiersState::empty()
+    });
+    assert_eq!(d.app.palette_clicks, 1);
+    assert_eq!(d.frame_count(), 1);
+    let px = d.window(palette).unwrap().read_pixels().expect("read palette pixels");
+    assert_eq!(px.pixel(5, 5), [0, 0, 128, 255]);
+    assert_eq!(d.frame().pixel(5, 5), [255, 255, 255, 255]);
+
+    // closing the palette leaves the app running
+    d.send_to(palette, Event::CloseRequested);
+    assert!(!d.exited());
+    assert_eq!(d.window_ids(), vec![WindowId::MAIN]);
+    assert!(d.window(palette).is_none());
+    d.send_to(palette, Event::CloseRequested);
+    assert!(!d.exited());
+
+    d.close();
+    assert!(d.exited());
+}